@@ -0,0 +1,55 @@
+//! Compares `Arc` and `BiasedArc`.
+//!
+//! Run with `cargo bench --bench arc`.
+
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cs431_homework::{Arc, BiasedArc};
+
+const ITER: usize = 1 << 22;
+const THREADS: usize = 4;
+
+/// Clones and drops a pointer in the owner thread.
+fn owner<P: Clone>(ptr: &P) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITER {
+        drop(black_box(ptr.clone()));
+    }
+    start.elapsed()
+}
+
+/// Clones and drops a pointer in the owner thread, while the other threads do so for their clones.
+fn shared<P: Clone + Send>(ptr: &P) -> Duration {
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            let ptr = ptr.clone();
+            let _ = s.spawn(move || {
+                for _ in 0..ITER / THREADS {
+                    drop(black_box(ptr.clone()));
+                }
+            });
+        }
+        for _ in 0..ITER {
+            drop(black_box(ptr.clone()));
+        }
+    });
+    start.elapsed()
+}
+
+fn report(name: &str, arc: Duration, biased: Duration) {
+    println!(
+        "{name:<8} Arc: {:>8.2} ns/op  BiasedArc: {:>8.2} ns/op",
+        arc.as_nanos() as f64 / ITER as f64,
+        biased.as_nanos() as f64 / ITER as f64,
+    );
+}
+
+fn main() {
+    let arc = Arc::new(0usize);
+    let biased = BiasedArc::new(0usize);
+    report("owner", owner(&arc), owner(&biased));
+    report("shared", shared(&arc), shared(&biased));
+}
@@ -2,9 +2,12 @@
 //!
 //! See the [`Arc<T>`][Arc] documentation for more details.
 
+use std::alloc::{self, Layout};
+use std::error::Error;
 use std::marker::PhantomData;
+use std::mem::{ManuallyDrop, MaybeUninit};
 use std::ops::Deref;
-use std::ptr::NonNull;
+use std::ptr::{self, NonNull};
 #[cfg(not(feature = "check-loom"))]
 use std::sync::atomic::{AtomicUsize, Ordering, fence};
 use std::{fmt, mem};
@@ -152,31 +155,86 @@ const MAX_REFCOUNT: usize = (isize::MAX) as usize;
 /// counting in general.
 ///
 /// [rc_examples]: std::rc#examples
-pub struct Arc<T> {
+pub struct Arc<T: ?Sized> {
     ptr: NonNull<ArcInner<T>>,
     phantom: PhantomData<ArcInner<T>>,
 }
 
-unsafe impl<T: Sync + Send> Send for Arc<T> {}
-unsafe impl<T: Sync + Send> Sync for Arc<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Send for Arc<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for Arc<T> {}
 
-impl<T> Arc<T> {
+impl<T: ?Sized> Arc<T> {
     fn from_inner(ptr: NonNull<ArcInner<T>>) -> Self {
         Self {
             ptr,
             phantom: PhantomData,
         }
     }
+
+    /// Makes a `Weak` that takes over a weak count of the allocation, e.g., the one held by the
+    /// `Arc`s. The data must be valid, as the layout of the allocation is computed from it.
+    fn weak_of_count(this: &Self) -> Weak<T> {
+        Weak {
+            ptr: this.ptr,
+            layout: Layout::for_value(this.inner()),
+        }
+    }
 }
 
-struct ArcInner<T> {
+/// `Weak` is a version of [`Arc`] that holds a non-owning reference to the
+/// managed allocation. The allocation is accessed by calling [`upgrade`][Weak::upgrade] on the
+/// `Weak` pointer, which returns an <code>[Option]<[Arc]\<T>></code>.
+///
+/// Since a `Weak` reference does not count towards ownership, it will not
+/// prevent the value stored in the allocation from being dropped, and `Weak` itself makes no
+/// guarantees about the value still being present. Thus it may return [`None`]
+/// when [`upgrade`][Weak::upgrade]d. Note however that a `Weak` reference *does* prevent the
+/// allocation itself (the backing store) from being deallocated.
+///
+/// A `Weak` pointer is useful for keeping a temporary reference to the allocation
+/// managed by [`Arc`] without preventing its inner value from being dropped. It is also used to
+/// prevent circular references between [`Arc`] pointers, e.g., the back-pointers from children to
+/// their parents in a tree.
+///
+/// # Examples
+///
+/// ```
+/// use cs431_homework::{Arc, Weak};
+///
+/// let five = Arc::new(5);
+/// let weak_five: Weak<i32> = Arc::downgrade(&five);
+/// assert_eq!(*weak_five.upgrade().unwrap(), 5);
+///
+/// drop(five);
+/// assert!(weak_five.upgrade().is_none());
+/// ```
+pub struct Weak<T: ?Sized> {
+    // A `Weak` made by `Weak::new` does not allocate, and `ptr` is `usize::MAX` (see
+    // `is_dangling`).
+    ptr: NonNull<ArcInner<T>>,
+    // The layout of the allocation, computed while the data is valid. Stable Rust cannot compute
+    // it from `ptr` alone, and the data may be dropped (or not yet written by `new_cyclic`) when
+    // the allocation is freed.
+    layout: Layout,
+}
+
+unsafe impl<T: ?Sized + Sync + Send> Send for Weak<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for Weak<T> {}
+
+// `repr(C)` fixes the offset of `data`, so that the layout of `ArcInner<T>` for unsized `T` can be
+// computed from that of `T` (see `allocate_for_layout`).
+#[repr(C)]
+struct ArcInner<T: ?Sized> {
     count: AtomicUsize,
+    // The number of `Weak`s, plus one if there are any `Arc`s. It is `usize::MAX` while
+    // `is_unique` checks the uniqueness.
+    weak: AtomicUsize,
     data: T,
 }
 
 // count and data are both sync/send
-unsafe impl<T: Sync + Send> Send for ArcInner<T> {}
-unsafe impl<T: Sync + Send> Sync for ArcInner<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Send for ArcInner<T> {}
+unsafe impl<T: ?Sized + Sync + Send> Sync for ArcInner<T> {}
 
 impl<T> Arc<T> {
     /// Constructs a new `Arc<T>`.
@@ -184,11 +242,200 @@ impl<T> Arc<T> {
     pub fn new(data: T) -> Arc<T> {
         let x = Box::new(ArcInner {
             count: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
             data,
         });
         Self::from_inner(Box::leak(x).into())
     }
 
+    /// Constructs a new `Arc<T>`, returning an error if the allocation fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let five = Arc::try_new(5)?;
+    /// # Ok::<(), cs431_homework::AllocError>(())
+    /// ```
+    pub fn try_new(data: T) -> Result<Arc<T>, AllocError> {
+        let inner = Self::try_allocate_for_layout(Layout::new::<T>(), |mem| mem.cast())?;
+        unsafe {
+            ptr::write(&raw mut (*inner).data, data);
+            Ok(Self::from_inner(NonNull::new_unchecked(inner)))
+        }
+    }
+
+    /// Constructs a new `Arc<T>` while giving you a [`Weak<T>`] to the allocation, to allow you
+    /// to construct a `T` which holds a weak pointer to itself.
+    ///
+    /// Calling [`upgrade`][Weak::upgrade] on the weak reference inside `data_fn` will fail and
+    /// result in a `None` value, as the data is not constructed yet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::{Arc, Weak};
+    ///
+    /// struct Gadget {
+    ///     me: Weak<Gadget>,
+    /// }
+    ///
+    /// let gadget = Arc::new_cyclic(|me| {
+    ///     assert!(me.upgrade().is_none());
+    ///     Gadget { me: me.clone() }
+    /// });
+    /// assert!(Arc::ptr_eq(&gadget, &gadget.me.upgrade().unwrap()));
+    /// ```
+    pub fn new_cyclic<F>(data_fn: F) -> Arc<T>
+    where
+        F: FnOnce(&Weak<T>) -> T,
+    {
+        let inner = Self::allocate_for_layout(Layout::new::<T>(), |mem| mem.cast());
+        // No `Arc` until the data is written, so that the `Weak`s fail to upgrade.
+        unsafe { ptr::write(&raw mut (*inner).count, AtomicUsize::new(0)) };
+        // This `Weak` becomes the one held by the `Arc`s. If `data_fn` panics, it frees the
+        // allocation.
+        let weak = Weak {
+            ptr: unsafe { NonNull::new_unchecked(inner) },
+            layout: Layout::new::<ArcInner<T>>(),
+        };
+        let data = data_fn(&weak);
+        unsafe {
+            ptr::write(&raw mut (*inner).data, data);
+            // Synchronizes with the `upgrade`s of the `Weak`s given to the other threads.
+            (*inner).count.store(1, Ordering::Release);
+        }
+        mem::forget(weak);
+        Self::from_inner(unsafe { NonNull::new_unchecked(inner) })
+    }
+
+    /// Constructs a new `Arc` with uninitialized contents, which can be initialized in place
+    /// through [`get_mut`][Arc::get_mut].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let mut five = Arc::<u32>::new_uninit();
+    /// Arc::get_mut(&mut five).unwrap().write(5);
+    /// let five = unsafe { five.assume_init() };
+    ///
+    /// assert_eq!(*five, 5)
+    /// ```
+    pub fn new_uninit() -> Arc<MaybeUninit<T>> {
+        let inner =
+            Arc::<MaybeUninit<T>>::allocate_for_layout(Layout::new::<T>(), |mem| mem.cast());
+        Arc::from_inner(unsafe { NonNull::new_unchecked(inner) })
+    }
+
+    /// Returns the inner value, if the given `Arc` is unique.
+    ///
+    /// Otherwise, an `Err` is returned with the same `Arc` that was passed in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let x = Arc::new(3);
+    /// assert_eq!(Arc::try_unwrap(x).unwrap(), 3);
+    ///
+    /// let x = Arc::new(4);
+    /// let _y = Arc::clone(&x);
+    /// assert_eq!(*Arc::try_unwrap(x).unwrap_err(), 4);
+    /// ```
+    #[inline]
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        // Zeroing the count makes the `Weak`s fail to upgrade.
+        if this
+            .inner()
+            .count
+            .compare_exchange(1, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(this);
+        }
+        // Synchronizes with the decrements of the other `Arc`s.
+        fence(Ordering::Acquire);
+
+        // The `Weak` held by the `Arc`s frees the allocation if there are no other `Weak`s.
+        let _weak = Self::weak_of_count(&this);
+        let data = unsafe { ptr::read(&this.inner().data) };
+        mem::forget(this);
+        Ok(data)
+    }
+}
+
+impl<T: ?Sized> Arc<T> {
+    /// Creates a new [`Weak`] pointer to this allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    ///
+    /// let weak_five = Arc::downgrade(&five);
+    /// ```
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        let weak = &this.inner().weak;
+        let mut current = weak.load(Ordering::Relaxed);
+        loop {
+            // `is_unique` is checking the uniqueness of `this`, which will fail.
+            if current == usize::MAX {
+                #[cfg(feature = "check-loom")]
+                loom::sync::atomic::spin_loop_hint();
+                current = weak.load(Ordering::Relaxed);
+                continue;
+            }
+            assert!(current < MAX_REFCOUNT);
+            // Acquire synchronizes with the release in `is_unique`, so that the `Weak` is not
+            // counted before the check.
+            match weak.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Self::weak_of_count(this),
+                Err(old) => current = old,
+            }
+        }
+    }
+
+    /// Gets the number of [`Weak`] pointers to this allocation.
+    ///
+    /// # Safety
+    ///
+    /// This method by itself is safe, but using it correctly requires extra care.
+    /// Another thread can change the weak count at any time,
+    /// including potentially between calling this method and acting on the result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let _weak_five = Arc::downgrade(&five);
+    ///
+    /// // This assertion is deterministic because we haven't shared
+    /// // the `Arc` or `Weak` between threads.
+    /// assert_eq!(1, Arc::weak_count(&five));
+    /// ```
+    #[inline]
+    pub fn weak_count(this: &Self) -> usize {
+        match this.inner().weak.load(Ordering::Acquire) {
+            // `is_unique` succeeds only if there are no `Weak`s.
+            usize::MAX => 0,
+            // Excludes the one held by the `Arc`s.
+            count => count - 1,
+        }
+    }
+
     /// Returns a mutable reference into the given `Arc` if there are
     /// no other `Arc`. Otherwise, return `None`.
     ///
@@ -209,18 +456,31 @@ impl<T> Arc<T> {
     /// ```
     #[inline]
     pub fn get_mut(this: &mut Self) -> Option<&mut T> {
-        if this.inner().count.load(Ordering::Acquire) == 1 {
+        if this.is_unique() {
             Some(unsafe { Self::get_mut_unchecked(this) })
         } else {
             None
         }
     }
 
-    // Used in `get_mut` and `make_mut` to check if the given `Arc` is the unique reference to the
+    // Used in `get_mut` to check if the given `Arc` is the unique reference to the
     // underlying data.
+    //
+    // The weak count is locked so that no `Weak` is upgraded during the check. Then there is no
+    // other `Arc` if the count is 1, and no `Weak` can upgrade because there is none left.
     #[inline]
     fn is_unique(&mut self) -> bool {
-        self.inner().count.load(Ordering::Acquire) == 1
+        if self
+            .inner()
+            .weak
+            .compare_exchange(1, usize::MAX, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        let unique = self.inner().count.load(Ordering::Acquire) == 1;
+        self.inner().weak.store(1, Ordering::Release);
+        unique
     }
 
     /// Returns a mutable reference into the given `Arc` without any check.
@@ -301,44 +561,59 @@ impl<T> Arc<T> {
     /// ```
     #[inline]
     pub fn ptr_eq(this: &Self, other: &Self) -> bool {
-        std::ptr::eq(this.ptr.as_ptr(), other.ptr.as_ptr())
+        // The metadata of unsized `T` (e.g., vtables) is not compared.
+        ptr::addr_eq(this.ptr.as_ptr(), other.ptr.as_ptr())
     }
+}
 
-    /// Returns the inner value, if the given `Arc` is unique.
+impl<T> Arc<MaybeUninit<T>> {
+    /// Converts to `Arc<T>`.
     ///
-    /// Otherwise, an `Err` is returned with the same `Arc` that was passed in.
+    /// # Safety
+    ///
+    /// The inner value must be initialized. See [`MaybeUninit::assume_init`].
+    pub unsafe fn assume_init(self) -> Arc<T> {
+        let inner = self.ptr.cast::<ArcInner<T>>();
+        mem::forget(self);
+        Arc::from_inner(inner)
+    }
+}
+
+impl<T: Clone> Arc<T> {
+    /// Returns the inner value if the given `Arc` is unique. Otherwise, clones the inner value.
     ///
     /// # Examples
     ///
     /// ```
     /// use cs431_homework::Arc;
     ///
-    /// let x = Arc::new(3);
-    /// assert_eq!(Arc::try_unwrap(x).unwrap(), 3);
+    /// let inner = String::from("test");
+    /// let ptr = inner.as_ptr();
     ///
-    /// let x = Arc::new(4);
-    /// let _y = Arc::clone(&x);
-    /// assert_eq!(*Arc::try_unwrap(x).unwrap_err(), 4);
+    /// let arc = Arc::new(inner);
+    /// let inner = Arc::unwrap_or_clone(arc);
+    /// // The inner value was not cloned
+    /// assert!(std::ptr::eq(ptr, inner.as_ptr()));
+    ///
+    /// let arc = Arc::new(inner);
+    /// let arc2 = arc.clone();
+    /// let inner = Arc::unwrap_or_clone(arc);
+    /// // Because there were 2 references, we had to clone the inner value.
+    /// assert!(!std::ptr::eq(ptr, inner.as_ptr()));
     /// ```
-    #[inline]
-    pub fn try_unwrap(this: Self) -> Result<T, Self> {
-        if Self::count(&this) == 1 {
-            let res = Ok(unsafe { Box::from_raw(this.ptr.as_ptr()).data });
-            mem::forget(this);
-            res
-        } else {
-            Err(this)
-        }
+    pub fn unwrap_or_clone(this: Self) -> T {
+        Self::try_unwrap(this).unwrap_or_else(|arc| (*arc).clone())
     }
-}
 
-impl<T: Clone> Arc<T> {
     /// Makes a mutable reference into the given `Arc`.
     ///
     /// If there are other `Arc` to the same allocation, then `make_mut` will create a new
     /// allocation and invoke `clone` on the inner value to ensure unique ownership. This is also
     /// referred to as clone-on-write.
     ///
+    /// If there are no other `Arc` but some [`Weak`] pointers, then the [`Weak`] pointers will be
+    /// disassociated and the inner value will not be cloned.
+    ///
     /// See also `get_mut`, which will fail rather than cloning.
     ///
     /// # Examples
@@ -367,23 +642,34 @@ impl<T: Clone> Arc<T> {
            }
            struct ArcInner<T> {
                count: AtomicUsize,
+               weak: AtomicUsize,
                data: T,
            }
         */
-        if !this.is_unique() {
-            *this = Self::from_inner(
-                NonNull::new(Box::into_raw(Box::new(ArcInner {
-                    count: AtomicUsize::new(1),
-                    data: this.inner().data.clone(),
-                })))
-                .unwrap(),
-            );
+        // Zeroing the count makes the `Weak`s fail to upgrade, as in `try_unwrap`.
+        if this
+            .inner()
+            .count
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Other `Arc`s exist, so clone the data.
+            *this = Self::new(this.inner().data.clone());
+        } else if this.inner().weak.load(Ordering::Relaxed) != 1 {
+            // Only `Weak`s remain, so move the data to a new allocation and leave them behind.
+            let _weak = Self::weak_of_count(this);
+            let data = unsafe { ptr::read(&this.inner().data) };
+            // The old `Arc` is already released by zeroing the count, so it must not be dropped.
+            unsafe { ptr::write(this, Self::new(data)) };
+        } else {
+            // No other `Arc` or `Weak`, so restore the count.
+            this.inner().count.store(1, Ordering::Release);
         }
         unsafe { Self::get_mut_unchecked(this) }
     }
 }
 
-impl<T> Clone for Arc<T> {
+impl<T: ?Sized> Clone for Arc<T> {
     /// Makes a clone of the `Arc` pointer.
     ///
     /// This creates another pointer to the same allocation, increasing the
@@ -409,7 +695,7 @@ impl<T> Clone for Arc<T> {
     }
 }
 
-impl<T> Deref for Arc<T> {
+impl<T: ?Sized> Deref for Arc<T> {
     type Target = T;
 
     #[inline]
@@ -418,7 +704,7 @@ impl<T> Deref for Arc<T> {
     }
 }
 
-impl<T> Drop for Arc<T> {
+impl<T: ?Sized> Drop for Arc<T> {
     /// Drops the `Arc`.
     ///
     /// This will decrement the reference count. If the reference
@@ -452,28 +738,464 @@ impl<T> Drop for Arc<T> {
 
         // pass the test in check-loom mode
         if self.inner().count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // the `Weak` held by the `Arc`s, made before the data is dropped
+            let weak = Self::weak_of_count(self);
             unsafe {
                 // drop the inner value
-                drop(Box::from_raw(self.ptr.as_ptr()));
+                ptr::drop_in_place(Self::get_mut_unchecked(self));
+                // release the `Weak` held by the `Arc`s, which frees the allocation if it is the
+                // last one
+                drop(weak);
                 println!("dropped!")
             }
         }
     }
 }
 
-impl<T: fmt::Display> fmt::Display for Arc<T> {
+impl<T> Weak<T> {
+    /// Constructs a new `Weak<T>`, without allocating any memory.
+    /// Calling [`upgrade`][Weak::upgrade] on the return value always gives [`None`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Weak;
+    ///
+    /// let empty: Weak<i64> = Weak::new();
+    /// assert!(empty.upgrade().is_none());
+    /// ```
+    pub const fn new() -> Weak<T> {
+        Weak {
+            ptr: unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(usize::MAX)) },
+            layout: Layout::new::<()>(),
+        }
+    }
+}
+
+impl<T: ?Sized> Weak<T> {
+    fn is_dangling(&self) -> bool {
+        self.ptr.as_ptr().cast::<()>().addr() == usize::MAX
+    }
+
+    /// Returns the counts of the allocation, or `None` if it is made by `Weak::new`.
+    ///
+    /// The data may be already dropped, so we do not create a reference to the whole `ArcInner`.
+    fn counts(&self) -> Option<(&AtomicUsize, &AtomicUsize)> {
+        if self.is_dangling() {
+            return None;
+        }
+        let inner = self.ptr.as_ptr();
+        Some(unsafe { (&(*inner).count, &(*inner).weak) })
+    }
+
+    /// Attempts to upgrade the `Weak` pointer to an [`Arc`], delaying
+    /// dropping of the inner value if successful.
+    ///
+    /// Returns [`None`] if the inner value has since been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    ///
+    /// let weak_five = Arc::downgrade(&five);
+    ///
+    /// let strong_five: Option<Arc<_>> = weak_five.upgrade();
+    /// assert!(strong_five.is_some());
+    ///
+    /// // Destroy all strong pointers.
+    /// drop(strong_five);
+    /// drop(five);
+    ///
+    /// assert!(weak_five.upgrade().is_none());
+    /// ```
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        let (count, _) = self.counts()?;
+        let mut current = count.load(Ordering::Relaxed);
+        loop {
+            // The data is dropped (or being unwrapped) once the count reaches 0.
+            if current == 0 {
+                return None;
+            }
+            assert!(current < MAX_REFCOUNT);
+            // Acquire synchronizes with the release in `make_mut`, which restores the count.
+            match count.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(Arc::from_inner(self.ptr)),
+                Err(old) => current = old,
+            }
+        }
+    }
+}
+
+impl<T> Default for Weak<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Clone for Weak<T> {
+    /// Makes a clone of the `Weak` pointer that points to the same allocation.
+    #[inline]
+    fn clone(&self) -> Weak<T> {
+        if let Some((_, weak)) = self.counts() {
+            // `is_unique` cannot be checking, as this `Weak` exists.
+            let _ = weak.fetch_add(1, Ordering::Relaxed);
+        }
+        Weak {
+            ptr: self.ptr,
+            layout: self.layout,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for Weak<T> {
+    /// Drops the `Weak` pointer. The allocation is freed if this is the last `Weak` and there are
+    /// no `Arc`s.
+    fn drop(&mut self) {
+        let Some((_, weak)) = self.counts() else {
+            return;
+        };
+        if weak.fetch_sub(1, Ordering::Release) == 1 {
+            // Synchronizes with the decrements of the other `Weak`s.
+            fence(Ordering::Acquire);
+            unsafe { alloc::dealloc(self.ptr.as_ptr().cast(), self.layout) };
+        }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Weak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(Weak)")
+    }
+}
+
+impl<T: ?Sized> Arc<T> {
+    /// Allocates an `ArcInner<T>` whose data has the layout `value_layout`, with the counts
+    /// initialized and the data uninitialized. `mem_to_arcinner` makes the pointer to the
+    /// `ArcInner<T>` from the allocation, giving the metadata of unsized `T`.
+    fn allocate_for_layout(
+        value_layout: Layout,
+        mem_to_arcinner: impl FnOnce(*mut u8) -> *mut ArcInner<T>,
+    ) -> *mut ArcInner<T> {
+        let layout = Self::layout_for(value_layout);
+        Self::try_allocate_for_layout(value_layout, mem_to_arcinner)
+            .unwrap_or_else(|_| alloc::handle_alloc_error(layout))
+    }
+
+    /// Like `allocate_for_layout`, but returns an error if the allocation fails.
+    fn try_allocate_for_layout(
+        value_layout: Layout,
+        mem_to_arcinner: impl FnOnce(*mut u8) -> *mut ArcInner<T>,
+    ) -> Result<*mut ArcInner<T>, AllocError> {
+        let mem = unsafe { alloc::alloc(Self::layout_for(value_layout)) };
+        if mem.is_null() {
+            return Err(AllocError);
+        }
+        let inner = mem_to_arcinner(mem);
+        unsafe {
+            ptr::write(&raw mut (*inner).count, AtomicUsize::new(1));
+            ptr::write(&raw mut (*inner).weak, AtomicUsize::new(1));
+        }
+        Ok(inner)
+    }
+
+    /// Returns the layout of `ArcInner<T>` whose data has the layout `value_layout`.
+    fn layout_for(value_layout: Layout) -> Layout {
+        Layout::new::<ArcInner<()>>()
+            .extend(value_layout)
+            .unwrap()
+            .0
+            .pad_to_align()
+    }
+
+    /// Consumes the `Arc`, returning the wrapped pointer. The pointer must be converted back to
+    /// an `Arc` using [`Arc::from_raw`] to avoid a memory leak.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let x = Arc::new("hello".to_owned());
+    /// let x_ptr = Arc::into_raw(x);
+    /// assert_eq!(unsafe { &*x_ptr }, "hello");
+    /// # drop(unsafe { Arc::from_raw(x_ptr) });
+    /// ```
+    pub fn into_raw(this: Self) -> *const T {
+        let ptr = Self::as_ptr(&this);
+        mem::forget(this);
+        ptr
+    }
+
+    /// Provides a raw pointer to the data, which is valid as long as there are strong counts.
+    pub fn as_ptr(this: &Self) -> *const T {
+        unsafe { &raw const (*this.ptr.as_ptr()).data }
+    }
+
+    /// Constructs an `Arc<T>` from a raw pointer returned by [`into_raw`][Arc::into_raw].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be returned by `Arc::<T>::into_raw`, and each call of `from_raw` takes over one
+    /// strong count of the allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let x = Arc::new("hello".to_owned());
+    /// let x_ptr = Arc::into_raw(x);
+    ///
+    /// unsafe {
+    ///     // Convert back to an `Arc` to prevent leak.
+    ///     let x = Arc::from_raw(x_ptr);
+    ///     assert_eq!(&*x, "hello");
+    /// }
+    /// ```
+    pub unsafe fn from_raw(ptr: *const T) -> Self {
+        // The offset of `data` depends only on the alignment of `T`, which is given by the
+        // metadata of `ptr` for unsized `T`.
+        let align = mem::align_of_val(unsafe { &*ptr });
+        let offset = Layout::new::<ArcInner<()>>()
+            .extend(Layout::from_size_align(0, align).unwrap())
+            .unwrap()
+            .1;
+        let inner = unsafe { ptr.byte_sub(offset) } as *mut ArcInner<T>;
+        Self::from_inner(unsafe { NonNull::new_unchecked(inner) })
+    }
+
+    /// Increments the strong count of the allocation of `ptr`, which is returned by
+    /// [`into_raw`][Arc::into_raw].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be returned by `Arc::<T>::into_raw`, and the allocation must be alive (i.e., the
+    /// strong count must be at least 1) during this call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let five = Arc::new(5);
+    /// let ptr = Arc::into_raw(five);
+    /// unsafe {
+    ///     Arc::increment_strong_count(ptr);
+    ///
+    ///     let five = Arc::from_raw(ptr);
+    ///     assert_eq!(2, Arc::count(&five));
+    ///     Arc::decrement_strong_count(ptr);
+    ///     assert_eq!(1, Arc::count(&five));
+    /// }
+    /// ```
+    pub unsafe fn increment_strong_count(ptr: *const T) {
+        let arc = ManuallyDrop::new(unsafe { Self::from_raw(ptr) });
+        mem::forget(Arc::clone(&arc));
+    }
+
+    /// Decrements the strong count of the allocation of `ptr`, which is returned by
+    /// [`into_raw`][Arc::into_raw]. The data is dropped if the count reaches 0.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be returned by `Arc::<T>::into_raw`, and the strong count must be at least 1
+    /// when this method is called. The count taken over by this call must not be used anymore.
+    pub unsafe fn decrement_strong_count(ptr: *const T) {
+        drop(unsafe { Self::from_raw(ptr) });
+    }
+
+    /// Converts `Arc<T>` to `Arc<U>` with `coerce`, an unsizing coercion of the pointer to the
+    /// data. Use [`unsize_arc!`] instead, which gives a safe `coerce`.
+    ///
+    /// Stable Rust does not allow implementing `CoerceUnsized` for `Arc`, so that `Arc<T>` is not
+    /// implicitly coerced to `Arc<dyn Trait>` as `Box<T>` is.
+    ///
+    /// # Safety
+    ///
+    /// `coerce` must return its argument, only with the type coerced.
+    ///
+    /// [`unsize_arc!`]: crate::unsize_arc
+    pub unsafe fn unsize<U: ?Sized>(
+        this: Self,
+        coerce: impl FnOnce(*const T) -> *const U,
+    ) -> Arc<U> {
+        let inner = this.ptr.as_ptr().cast_const();
+        mem::forget(this);
+        let data = unsafe { &raw const (*inner).data };
+        // The offset of `data` is kept by the coercion, as the alignment of the data is.
+        let offset = unsafe { data.byte_offset_from(inner) } as usize;
+        let inner = unsafe { coerce(data).byte_sub(offset) } as *const ArcInner<U>;
+        Arc::from_inner(unsafe { NonNull::new_unchecked(inner.cast_mut()) })
+    }
+}
+
+/// Converts an `Arc<T>` to an `Arc<U>` by an unsizing coercion, e.g., from `Arc<[T; N]>` to
+/// `Arc<[T]>`, or from `Arc<T>` to `Arc<dyn Trait>`. The second argument is `U`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fmt::Debug;
+/// use cs431_homework::{Arc, unsize_arc};
+///
+/// let debug: Arc<dyn Debug> = unsize_arc!(Arc::new(5), dyn Debug);
+/// assert_eq!(format!("{debug:?}"), "5");
+///
+/// let slice = unsize_arc!(Arc::new([1, 2, 3]), [i32]);
+/// assert_eq!(&*slice, [1, 2, 3]);
+/// ```
+#[macro_export]
+macro_rules! unsize_arc {
+    ($arc:expr, $ty:ty) => {
+        match $arc {
+            // SAFETY: the closure returns its argument, coerced to the return type. Only unsizing
+            // coercions are allowed between the raw pointers.
+            arc => unsafe { $crate::Arc::unsize(arc, |data| -> *const $ty { data }) },
+        }
+    };
+}
+
+impl<T> Arc<[T]> {
+    /// Allocates an `ArcInner<[T]>` of length `len`, with the elements uninitialized.
+    fn allocate_for_slice(len: usize) -> *mut ArcInner<[T]> {
+        Self::allocate_for_layout(Layout::array::<T>(len).unwrap(), |mem| {
+            ptr::slice_from_raw_parts_mut(mem.cast::<T>(), len) as *mut ArcInner<[T]>
+        })
+    }
+}
+
+impl<T> From<Vec<T>> for Arc<[T]> {
+    /// Allocates a reference-counted slice and moves `v`'s items into it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let unique: Vec<i32> = vec![1, 2, 3];
+    /// let shared: Arc<[i32]> = Arc::from(unique);
+    /// assert_eq!(&[1, 2, 3], &shared[..]);
+    /// ```
+    fn from(mut v: Vec<T>) -> Arc<[T]> {
+        let inner = Self::allocate_for_slice(v.len());
+        unsafe {
+            ptr::copy_nonoverlapping(v.as_ptr(), (&raw mut (*inner).data).cast::<T>(), v.len());
+            // The items are moved, so only the buffer of `v` is freed.
+            v.set_len(0);
+            Self::from_inner(NonNull::new_unchecked(inner))
+        }
+    }
+}
+
+impl<T: Clone> From<&[T]> for Arc<[T]> {
+    /// Allocates a reference-counted slice and fills it by cloning `v`'s items.
+    fn from(v: &[T]) -> Arc<[T]> {
+        /// Drops the items cloned so far and frees the allocation, if a `clone` panics.
+        struct Guard<T> {
+            mem: *mut u8,
+            layout: Layout,
+            elems: *mut T,
+            len: usize,
+        }
+
+        impl<T> Drop for Guard<T> {
+            fn drop(&mut self) {
+                unsafe {
+                    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.elems, self.len));
+                    alloc::dealloc(self.mem, self.layout);
+                }
+            }
+        }
+
+        let inner = Self::allocate_for_slice(v.len());
+        let elems = unsafe { (&raw mut (*inner).data).cast::<T>() };
+        let mut guard = Guard {
+            mem: inner.cast::<u8>(),
+            layout: Self::layout_for(Layout::array::<T>(v.len()).unwrap()),
+            elems,
+            len: 0,
+        };
+        for (i, item) in v.iter().enumerate() {
+            unsafe { elems.add(i).write(item.clone()) };
+            guard.len += 1;
+        }
+        mem::forget(guard);
+        Self::from_inner(unsafe { NonNull::new_unchecked(inner) })
+    }
+}
+
+impl From<&str> for Arc<str> {
+    /// Allocates a reference-counted string slice and copies `v` into it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cs431_homework::Arc;
+    ///
+    /// let shared: Arc<str> = Arc::from("eggplant");
+    /// assert_eq!("eggplant", &shared[..]);
+    /// ```
+    fn from(v: &str) -> Arc<str> {
+        // SAFETY: the bytes of `v` are valid UTF-8.
+        unsafe { Self::from_utf8_unchecked(Arc::from(v.as_bytes())) }
+    }
+}
+
+impl From<String> for Arc<str> {
+    /// Allocates a reference-counted `str` and moves the bytes of `v` into it.
+    fn from(v: String) -> Arc<str> {
+        // SAFETY: the bytes of `v` are valid UTF-8.
+        unsafe { Self::from_utf8_unchecked(Arc::from(v.into_bytes())) }
+    }
+}
+
+impl Arc<str> {
+    /// Converts `bytes` to a string slice without checking that it is valid UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must be valid UTF-8.
+    unsafe fn from_utf8_unchecked(bytes: Arc<[u8]>) -> Self {
+        let inner = bytes.ptr.as_ptr() as *mut ArcInner<str>;
+        mem::forget(bytes);
+        // SAFETY: `str` has the same layout as `[u8]`.
+        Self::from_inner(unsafe { NonNull::new_unchecked(inner) })
+    }
+}
+
+/// The error type of [`Arc::try_new`], which indicates that the allocation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+
+impl Error for AllocError {}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for Arc<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Display::fmt(&**self, f)
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for Arc<T> {
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Arc<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Debug::fmt(&**self, f)
     }
 }
 
-impl<T> fmt::Pointer for Arc<T> {
+impl<T: ?Sized> fmt::Pointer for Arc<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Pointer::fmt(&(&**self), f)
     }
@@ -0,0 +1,157 @@
+//! Atomically swappable `Arc`.
+
+use core::fmt;
+#[cfg(not(feature = "check-loom"))]
+use core::hint;
+use core::marker::PhantomData;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::Arc;
+use crate::hazard_pointer::{HAZARDS, Shield, retire};
+
+/// A cell holding an [`Arc<T>`][Arc] that can be loaded and replaced by many threads without
+/// locking.
+///
+/// The cell points to a heap-allocated `Arc<T>`, which is protected by hazard pointers while being
+/// cloned by `load`. When the cell is updated, the `Arc<T>` is moved out only after no `load` can
+/// clone it: `swap` and `compare_exchange` wait for it, and `store` retires it.
+///
+/// # Examples
+///
+/// ```
+/// use cs431_homework::{Arc, AtomicArc};
+///
+/// let config = AtomicArc::new(Arc::new(String::from("v1")));
+/// let old = config.load();
+///
+/// // hot-reload
+/// config.store(Arc::new(String::from("v2")));
+/// assert_eq!(*old, "v1");
+/// assert_eq!(*config.load(), "v2");
+/// ```
+pub struct AtomicArc<T: ?Sized> {
+    ptr: AtomicPtr<Arc<T>>,
+    _marker: PhantomData<Arc<T>>,
+}
+
+// The `Arc<T>`s are cloned and dropped by any threads.
+unsafe impl<T: ?Sized + Send + Sync> Send for AtomicArc<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AtomicArc<T> {}
+
+impl<T: ?Sized + Send + Sync> AtomicArc<T> {
+    /// Creates a new cell holding `arc`.
+    pub fn new(arc: Arc<T>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(arc))),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a clone of the `Arc` in the cell.
+    pub fn load(&self) -> Arc<T> {
+        let shield = Shield::default();
+        let current = shield.protect(&self.ptr);
+        // SAFETY: `current` is protected, so it is not freed and its `Arc` holds the count.
+        unsafe { (*current).clone() }
+    }
+
+    /// Stores `new` to the cell.
+    ///
+    /// The previous `Arc` is retired without waiting for the `load`s, so its count is decremented
+    /// later, possibly by another thread, when the retired pointers are collected.
+    pub fn store(&self, new: Arc<T>) {
+        let new = Box::into_raw(Box::new(new));
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        // SAFETY: `old` is unlinked by us.
+        unsafe { retire(old) };
+    }
+
+    /// Stores `new` to the cell, and returns the previous `Arc`.
+    ///
+    /// Waits until the concurrent `load`s finish cloning the previous `Arc`, so that it is returned
+    /// with no extra count, e.g., `Arc::try_unwrap` of it succeeds if there is no other `Arc`.
+    pub fn swap(&self, new: Arc<T>) -> Arc<T> {
+        let new = Box::into_raw(Box::new(new));
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        // SAFETY: `old` is unlinked by us.
+        unsafe { Self::take(old) }
+    }
+
+    /// Stores `new` to the cell if it holds the same allocation as `current` (see `Arc::ptr_eq`).
+    ///
+    /// Returns the previous `Arc` on success, with no extra count as in `swap`. Otherwise, returns
+    /// the `Arc` in the cell and `new`.
+    pub fn compare_exchange(
+        &self,
+        current: &Arc<T>,
+        new: Arc<T>,
+    ) -> Result<Arc<T>, (Arc<T>, Arc<T>)> {
+        let shield = Shield::default();
+        let new = Box::into_raw(Box::new(new));
+        loop {
+            let old = shield.protect(&self.ptr);
+            // SAFETY: `old` is protected.
+            let old_ref = unsafe { &*old };
+            if !Arc::ptr_eq(old_ref, current) {
+                // SAFETY: `new` is not shared.
+                return Err((old_ref.clone(), *unsafe { Box::from_raw(new) }));
+            }
+            // Retry if the cell is updated, even if it still holds `current` in another box.
+            if self
+                .ptr
+                .compare_exchange(old, new, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                drop(shield);
+                // SAFETY: `old` is unlinked by us.
+                return Ok(unsafe { Self::take(old) });
+            }
+        }
+    }
+
+    /// Waits until `old` is not protected by any `load`, and moves the `Arc` out of it.
+    ///
+    /// # Safety
+    ///
+    /// `old` must be unlinked by the current thread.
+    unsafe fn take(old: *mut Arc<T>) -> Arc<T> {
+        // A `load` that protects `old` after this fails to validate it, as it is unlinked.
+        while HAZARDS.is_protected(old.cast()) {
+            #[cfg(not(feature = "check-loom"))]
+            hint::spin_loop();
+            #[cfg(feature = "check-loom")]
+            loom::sync::atomic::spin_loop_hint();
+        }
+        // SAFETY: no one else accesses `old`.
+        *unsafe { Box::from_raw(old) }
+    }
+
+    /// Consumes the cell, returning the `Arc` in it.
+    pub fn into_inner(self) -> Arc<T> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        core::mem::forget(self);
+        // SAFETY: The cell is owned, so no other thread accesses `ptr`.
+        *unsafe { Box::from_raw(ptr) }
+    }
+}
+
+impl<T: ?Sized> Drop for AtomicArc<T> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let ptr = *self.ptr.get_mut();
+        #[cfg(feature = "check-loom")]
+        let ptr = self.ptr.load(Ordering::Relaxed);
+
+        drop(unsafe { Box::from_raw(ptr) });
+    }
+}
+
+impl<T: ?Sized + Send + Sync + fmt::Debug> fmt::Debug for AtomicArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AtomicArc").field(&self.load()).finish()
+    }
+}
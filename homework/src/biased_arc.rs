@@ -0,0 +1,302 @@
+//! Biased reference-counting pointers.
+//!
+//! See the [`BiasedArc<T>`][BiasedArc] documentation for more details.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicIsize, Ordering, fence};
+use core::{fmt, mem};
+use std::sync::{Arc, Mutex};
+
+/// `BiasedInner::shared` is set if the counts are merged.
+const MERGED: isize = 1;
+/// `BiasedInner::shared` is set if the object is queued to its owner.
+const QUEUED: isize = 2;
+/// A reference in `BiasedInner::shared`.
+const ONE: isize = 4;
+
+/// A thread-safe reference-counting pointer, biased towards the thread that created it.
+///
+/// Like [`Arc<T>`](crate::Arc), but the references cloned and dropped by the creator of the
+/// allocation (the *owner*) are counted by a non-atomic *biased* count, and only the others are
+/// counted by an atomic *shared* count. This makes `clone` and `drop` cheap if the references
+/// mostly stay in the owner thread.
+///
+/// The shared count becomes negative if the other threads drop the references cloned by the
+/// owner. The counts are merged into the shared count when the biased count reaches zero, or
+/// when the owner handles the queue of the allocations whose shared count became negative (see
+/// [`merge_queued`]), or by the other threads after the owner exits. After the merge, all threads
+/// use the shared count, and the data is dropped when it reaches zero.
+///
+/// # Examples
+///
+/// ```
+/// use cs431_homework::BiasedArc;
+/// use std::thread;
+///
+/// let five = BiasedArc::new(5);
+/// let cloned = five.clone(); // cheap
+///
+/// thread::spawn(move || assert_eq!(*cloned, 5)).join().unwrap();
+/// ```
+pub struct BiasedArc<T> {
+    ptr: NonNull<BiasedInner<T>>,
+    phantom: PhantomData<BiasedInner<T>>,
+}
+
+unsafe impl<T: Sync + Send> Send for BiasedArc<T> {}
+unsafe impl<T: Sync + Send> Sync for BiasedArc<T> {}
+
+struct BiasedInner<T> {
+    /// The queue of the owner thread, which also identifies the owner.
+    owner: Arc<Queue>,
+    /// The number of references cloned minus those dropped by the owner. Accessed only by the
+    /// owner, or by the other threads after the owner exits.
+    biased: Cell<usize>,
+    /// The number of references cloned minus those dropped by the other threads, times `ONE`,
+    /// with the `MERGED` and `QUEUED` flags.
+    shared: AtomicIsize,
+    data: T,
+}
+
+/// The allocations of a thread whose shared count became negative, to be merged by the thread.
+#[derive(Debug, Default)]
+struct Queue {
+    /// Whether `objects` is not empty, checked without locking.
+    pending: AtomicBool,
+    inner: Mutex<QueueInner>,
+}
+
+/// A type-erased pointer to a `BiasedInner<T>` with `merge::<T>`.
+type Object = (*const (), unsafe fn(*const ()));
+
+#[derive(Debug, Default)]
+struct QueueInner {
+    objects: Vec<Object>,
+    /// Whether the owner thread exited. Then the other threads merge the counts by themselves.
+    exited: bool,
+}
+
+// SAFETY: The objects are merged by one thread, see `BiasedArc::drop`.
+unsafe impl Send for QueueInner {}
+
+/// The queue of the current thread. Merges all queued allocations when the thread exits.
+struct Owner(Arc<Queue>);
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        let objects = {
+            let mut inner = self.0.inner.lock().unwrap();
+            inner.exited = true;
+            mem::take(&mut inner.objects)
+        };
+        for (object, merge) in objects {
+            unsafe { merge(object) };
+        }
+    }
+}
+
+thread_local! {
+    static OWNER: Owner = Owner(Arc::default());
+}
+
+/// Merges the counts of the allocations owned by the current thread, whose shared count became
+/// negative. Allocations whose references are all dropped are freed.
+///
+/// This is called when the current thread creates or drops a `BiasedArc`, and when it exits.
+pub fn merge_queued() {
+    let _ = OWNER.try_with(|owner| {
+        if !owner.0.pending.load(Ordering::Relaxed) {
+            return;
+        }
+        let objects = {
+            let mut inner = owner.0.inner.lock().unwrap();
+            owner.0.pending.store(false, Ordering::Relaxed);
+            mem::take(&mut inner.objects)
+        };
+        for (object, merge) in objects {
+            unsafe { merge(object) };
+        }
+    });
+}
+
+/// Merges the biased count into the shared count, and frees the allocation if no references are
+/// left.
+///
+/// # Safety
+///
+/// `object` must be a `BiasedInner<T>` that is not merged yet, and must be called by the owner, or
+/// by another thread after the owner exits.
+unsafe fn merge<T>(object: *const ()) {
+    let inner = unsafe { &*object.cast::<BiasedInner<T>>() };
+    let biased = inner.biased.replace(0) as isize;
+    // Acquire synchronizes with the decrements of the other threads.
+    let old = inner
+        .shared
+        .fetch_add(biased * ONE + MERGED, Ordering::AcqRel);
+    if (old >> 2) + biased == 0 {
+        drop(unsafe { Box::from_raw(object.cast::<BiasedInner<T>>().cast_mut()) });
+    }
+}
+
+impl<T> BiasedArc<T> {
+    /// Constructs a new `BiasedArc<T>` owned by the current thread.
+    pub fn new(data: T) -> BiasedArc<T> {
+        merge_queued();
+        let inner = Box::new(BiasedInner {
+            owner: OWNER.with(|owner| owner.0.clone()),
+            biased: Cell::new(1),
+            shared: AtomicIsize::new(0),
+            data,
+        });
+        Self {
+            ptr: Box::leak(inner).into(),
+            phantom: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn inner(&self) -> &BiasedInner<T> {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Returns `true` if the current thread owns the allocation and the counts are not merged.
+    #[inline]
+    fn is_biased(&self) -> bool {
+        let inner = self.inner();
+        // Only the owner sets `MERGED` while it is alive, so it reads its own write.
+        OWNER
+            .try_with(|owner| Arc::ptr_eq(&owner.0, &inner.owner))
+            .unwrap_or(false)
+            && inner.shared.load(Ordering::Relaxed) & MERGED == 0
+    }
+
+    /// Returns `true` if the two `BiasedArc`s point to the same allocation.
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        ptr::eq(this.ptr.as_ptr(), other.ptr.as_ptr())
+    }
+
+    /// Queues the allocation to its owner, or merges the counts if the owner exited.
+    fn queue(&self) {
+        let inner = self.inner();
+        let object: Object = (self.ptr.as_ptr().cast_const().cast(), merge::<T>);
+        let mut queue = inner.owner.inner.lock().unwrap();
+        if queue.exited {
+            // The owner does not access the biased count anymore, and the lock synchronizes with
+            // its last access.
+            drop(queue);
+            unsafe { merge::<T>(object.0) };
+        } else {
+            queue.objects.push(object);
+            inner.owner.pending.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T> Clone for BiasedArc<T> {
+    /// Makes a clone of the `BiasedArc` pointer, increasing the biased count if the current thread
+    /// is the owner, and the shared count otherwise.
+    #[inline]
+    fn clone(&self) -> BiasedArc<T> {
+        let inner = self.inner();
+        if self.is_biased() {
+            inner.biased.set(inner.biased.get() + 1);
+        } else {
+            let _ = inner.shared.fetch_add(ONE, Ordering::Relaxed);
+        }
+        Self {
+            ptr: self.ptr,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for BiasedArc<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner().data
+    }
+}
+
+impl<T> Drop for BiasedArc<T> {
+    /// Drops the `BiasedArc`, decreasing the biased count if the current thread is the owner,
+    /// and the shared count otherwise.
+    fn drop(&mut self) {
+        let inner = self.inner();
+        if self.is_biased() {
+            let biased = inner.biased.get() - 1;
+            inner.biased.set(biased);
+            if biased == 0 {
+                let mut old = inner.shared.load(Ordering::Relaxed);
+                loop {
+                    if old & QUEUED != 0 {
+                        // The allocation is merged by the queue, which may not have it yet.
+                        break;
+                    }
+                    // Acquire synchronizes with the decrements of the other threads.
+                    match inner.shared.compare_exchange_weak(
+                        old,
+                        old | MERGED,
+                        Ordering::AcqRel,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) if old >> 2 == 0 => {
+                            drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+                            return;
+                        }
+                        Ok(_) => return,
+                        Err(current) => old = current,
+                    }
+                }
+            }
+            // The other threads may have dropped the last references.
+            merge_queued();
+            return;
+        }
+
+        // The flag is set with the decrement, so that the allocation is queued once and not merged
+        // (and freed) before it is queued.
+        let mut old = inner.shared.load(Ordering::Relaxed);
+        let new = loop {
+            let mut new = old - ONE;
+            if new & MERGED == 0 && new >> 2 < 0 {
+                // The biased count includes the references dropped by the other threads, which only
+                // the owner (or a thread after the owner exits) can merge.
+                new |= QUEUED;
+            }
+            match inner
+                .shared
+                .compare_exchange_weak(old, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break new,
+                Err(current) => old = current,
+            }
+        };
+        if new & MERGED != 0 {
+            if new >> 2 == 0 {
+                // Synchronizes with the decrements of the other threads.
+                fence(Ordering::Acquire);
+                drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+            }
+        } else if new & QUEUED != 0 && old & QUEUED == 0 {
+            self.queue();
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for BiasedArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for BiasedArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
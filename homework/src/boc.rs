@@ -1,69 +1,138 @@
 //! Concurrent Owner (Cown) type.
 
-use core::cell::UnsafeCell;
-use core::sync::atomic::Ordering::{Relaxed, SeqCst};
+use core::cell::{RefCell, UnsafeCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
 use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+use core::task::{Context, Poll, Waker};
 use core::{fmt, hint, ptr};
 use std::backtrace::Backtrace;
+use std::collections::VecDeque;
 use std::mem;
-use std::sync::Arc;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, LazyLock, Mutex};
 
 use rayon::spawn;
 
+use crate::hello_server::ThreadPool;
+
+#[cfg(feature = "boc-trace")]
+pub mod trace;
+#[cfg(feature = "boc-trace")]
+use trace::{BehaviorTrace, Tracer};
+
+/// `Request::next` of a read that is granted but has no next request yet, so that the next read can
+/// also be granted when it is enqueued.
+const GRANTED_READ: *mut Request = ptr::without_provenance_mut(1);
+
+/// `Request::state` is set if the request is granted, i.e., the behavior may access the cown.
+const GRANTED: usize = 1;
+/// `Request::state` is set if the previous request for the cown is released.
+const PREV_RELEASED: usize = 2;
+/// `Request::state` is set if the behavior of the request is finished.
+const FINISHED: usize = 4;
+
 /// A request for a cown.
+///
+/// The requests for a cown are released in the order they are enqueued. A write request is granted
+/// when the previous request is released. A read request is granted either then, or when the
+/// previous request is a granted read, so that consecutive reads run in parallel.
 pub struct Request {
-    /// Pointer to the next scheduled behavior.
-    next: AtomicPtr<Behavior>,
+    /// Pointer to the next request for the cown, or `GRANTED_READ`.
+    next: AtomicPtr<Request>,
     /// Is this request scheduled?
     scheduled: AtomicBool,
+    /// The behavior of this request, set when it is enqueued.
+    behavior: AtomicPtr<Behavior>,
+    /// Is this request read-only?
+    read: bool,
+    /// The progress of this request, a set of `GRANTED`, `PREV_RELEASED` and `FINISHED`.
+    state: AtomicUsize,
     /// The cown that this request wants to access.
     ///
     /// This is an `Arc` as the all exposed `CownPtr`s may have been dropped while the behavior is
     /// still scheduled.
     target: Arc<dyn CownBase>,
+    /// The id of the behavior of the previous request for the cown, or `usize::MAX` if none.
+    #[cfg(feature = "boc-trace")]
+    dep: AtomicUsize,
 }
 
-// SAFETY: In the basic version of BoC, user cannot get shared reference through the [`CownBase`],
-// so `Sync` bound on it is not necessary.
+// SAFETY: The shared references to the value of a cown are given only by the read requests, which
+// require the value to be `Sync`. See `Read`.
 unsafe impl Send for Request {}
 
 impl Request {
     /// Creates a new Request.
-    fn new(target: Arc<dyn CownBase>) -> Request {
+    fn new(target: Arc<dyn CownBase>, read: bool) -> Request {
         Request {
             next: AtomicPtr::new(ptr::null_mut()),
             scheduled: AtomicBool::new(false),
+            behavior: AtomicPtr::new(ptr::null_mut()),
+            read,
+            state: AtomicUsize::new(0),
             target,
+            #[cfg(feature = "boc-trace")]
+            dep: AtomicUsize::new(usize::MAX),
         }
     }
 
     /// Start the first phase of the 2PL enqueue operation.
     ///
-    /// Enqueues `self` onto the `target` cown. Returns once all previous behaviors on this cown has
-    /// finished enqueueing on all of its required cowns. This ensures the 2PL protocol.
+    /// Enqueues `requests`, the requests of a batch for the same cown in order, onto the `target`
+    /// cown at once, so that no other requests are enqueued between them. Returns once all previous
+    /// behaviors on this cown has finished enqueueing on all of its required cowns. This ensures
+    /// the 2PL protocol.
     ///
     /// # SAFETY
     ///
-    /// `behavior` must be a valid raw pointer to the behavior for `self`, and this should be the
-    /// only enqueueing of this request and behavior.
-    unsafe fn start_enqueue(&self, behavior: *const Behavior) {
+    /// `requests` must be non-empty, each with a valid raw pointer to its behavior, and this should
+    /// be the only enqueueing of these requests.
+    unsafe fn start_enqueue(requests: &[(&Request, *const Behavior)]) {
+        let (first, _) = requests[0];
+        let (last, _) = requests[requests.len() - 1];
+        for &(r, b) in requests {
+            r.behavior.store(b.cast_mut(), Relaxed);
+        }
+        // The requests are not visible to the other threads until `last` is enqueued.
+        for w in requests.windows(2) {
+            let ((prev, _), (r, _)) = (w[0], w[1]);
+            prev.next.store(r as *const Self as *mut Self, SeqCst);
+            #[cfg(feature = "boc-trace")]
+            r.dep
+                .store(unsafe { (*prev.behavior.load(Relaxed)).id }, Relaxed);
+        }
         let prev = unsafe {
-            self.target
+            last.target
                 .last()
-                .swap(self as *const Self as *mut Self, SeqCst)
-                .as_mut()
+                .swap(last as *const Self as *mut Self, SeqCst)
+                .as_ref()
         };
         if let Some(prev) = prev {
             while !prev.scheduled.load(SeqCst) {
                 hint::spin_loop();
             }
+            // `prev` is not released before we set `prev.next`, so its behavior is valid.
+            #[cfg(feature = "boc-trace")]
+            first
+                .dep
+                .store(unsafe { (*prev.behavior.load(Relaxed)).id }, Relaxed);
             // notify the prev that current request is ready
-            prev.next.store(behavior as *mut Behavior, SeqCst);
+            let prev_next = prev.next.swap(first as *const Self as *mut Self, SeqCst);
+            // `prev` may be released from now on.
+            if prev_next == GRANTED_READ
+                && first.read
+                && first.state.fetch_or(GRANTED, SeqCst) & GRANTED == 0
+            {
+                unsafe { Request::grant(first) };
+            }
             return;
         }
         // no prev exist, it's ok to go.
+        first.state.store(GRANTED | PREV_RELEASED, SeqCst);
         unsafe {
-            Behavior::resolve_one(behavior);
+            Request::grant(first);
         }
     }
 
@@ -78,47 +147,107 @@ impl Request {
         self.scheduled.store(true, SeqCst);
     }
 
+    /// Grants the cown to `this`, and to the following reads that can run with `this`.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be a valid request whose `GRANTED` flag is just set by the caller.
+    unsafe fn grant(mut this: *const Self) {
+        loop {
+            let req = unsafe { &*this };
+            let mut next = ptr::null();
+            if req.read {
+                if let Err(req_next) =
+                    req.next
+                        .compare_exchange(ptr::null_mut(), GRANTED_READ, SeqCst, SeqCst)
+                {
+                    // The next request is enqueued, and it is not released before `req`.
+                    let req_next = unsafe { &*req_next };
+                    if req_next.read && req_next.state.fetch_or(GRANTED, SeqCst) & GRANTED == 0 {
+                        next = req_next as *const Self;
+                    }
+                }
+            }
+            // `req` may be released from now on.
+            unsafe { Behavior::resolve_one(req.behavior.load(Relaxed)) };
+            if next.is_null() {
+                return;
+            }
+            this = next;
+        }
+    }
+
     /// Release the cown to the next behavior.
     ///
     /// Called when `self` has been completed, and thus can allow the next waiting behavior to run.
-    /// If there is no next behavior, then the cown's tail pointer is set to null.
+    /// The requests are released in order, so the release of a read is deferred until the previous
+    /// request is released.
     ///
     /// # Safety
     ///
     /// `self` must have been actually completed.
     unsafe fn release(&self) {
-        if self.next.load(SeqCst).is_null() {
-            // (2)this is the last request for the cown,
-            if self
-                .target
-                .last()
-                .compare_exchange(
-                    self as *const Self as *mut Self,
-                    ptr::null_mut(),
-                    SeqCst,
-                    Relaxed,
-                )
-                .is_ok()
-            {
-                return;
+        if self.state.fetch_or(FINISHED, SeqCst) & PREV_RELEASED == 0 {
+            // The previous request (which is a read) releases `self` when it is released.
+            return;
+        }
+        unsafe { Request::release_in_order(self) };
+    }
+
+    /// Releases `this`, and the following finished requests.
+    ///
+    /// If there is no next behavior, then the cown's tail pointer is set to null.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be a finished request whose previous request is released.
+    unsafe fn release_in_order(mut this: *const Self) {
+        loop {
+            let req = unsafe { &*this };
+            let behavior = req.behavior.load(Relaxed);
+            let mut next = req.next.load(SeqCst);
+            if next.is_null() || next == GRANTED_READ {
+                // (2)this is the last request for the cown,
+                if req
+                    .target
+                    .last()
+                    .compare_exchange(this.cast_mut(), ptr::null_mut(), SeqCst, Relaxed)
+                    .is_ok()
+                {
+                    unsafe { Behavior::release_one(behavior) };
+                    return;
+                }
+                // (3) this is not the last request for the cown,
+                // wait for the next request to bet set
+                loop {
+                    next = req.next.load(SeqCst);
+                    if !next.is_null() && next != GRANTED_READ {
+                        break;
+                    }
+                    hint::spin_loop();
+                }
             }
-            // (3) this is not the last request for the cown,
-            // wait for the next request to bet set
-            while self.next.load(SeqCst).is_null() {
-                hint::spin_loop();
+            // (1)notify the successor that `req` is released
+            let state = unsafe { (*next).state.fetch_or(GRANTED | PREV_RELEASED, SeqCst) };
+            unsafe { Behavior::release_one(behavior) };
+            if state & GRANTED == 0 {
+                unsafe { Request::grant(next) };
             }
-        }
-        // (1)notify the successor to resolve one
-        unsafe {
-            Behavior::resolve_one(self.next.load(SeqCst));
+            if state & FINISHED == 0 {
+                // `next` releases itself when it is finished.
+                return;
+            }
+            this = next;
         }
     }
 }
 
 impl Ord for Request {
     fn cmp(&self, other: &Self) -> core::cmp::Ordering {
-        #[allow(warnings)]
-        Arc::as_ptr(&self.target).cmp(&Arc::as_ptr(&other.target))
+        // Compare the addresses only, as the vtables of a type may differ across codegen units.
+        Arc::as_ptr(&self.target)
+            .cast::<()>()
+            .cmp(&Arc::as_ptr(&other.target).cast::<()>())
     }
 }
 impl PartialOrd for Request {
@@ -148,10 +277,20 @@ type BehaviorThunk = Box<dyn FnOnce() + Send>;
 struct Behavior {
     /// The body of the Behavior.
     thunk: BehaviorThunk,
-    /// Number of not-yet enqueued requests.
+    /// Run if the thunk panics, after the cowns it writes to are poisoned.
+    on_panic: BehaviorThunk,
+    /// Number of not-yet enqueued requests, and then the number of not-yet released requests plus
+    /// one for the running thunk.
     count: AtomicUsize,
     /// The requests for this behavior.
     requests: Vec<Request>,
+    /// The runtime that runs the thunk.
+    runtime: BocRuntime,
+    /// The priority of the thunk in the scheduler.
+    priority: Priority,
+    /// The id of the behavior in the trace.
+    #[cfg(feature = "boc-trace")]
+    id: usize,
 }
 
 impl Behavior {
@@ -163,36 +302,84 @@ impl Behavior {
     {
         let mut requests = cowns.requests();
         requests.sort();
+        // A cown read more than once is requested once.
+        requests.dedup_by(|r, prev| r == prev && r.read && prev.read);
+        // Otherwise, the behavior would wait for itself, and the thunk would alias `&mut T`.
+        assert!(
+            requests.windows(2).all(|w| w[0] != w[1]),
+            "a cown is requested more than once in a behavior, and at least once for write"
+        );
         Self {
             thunk: Box::new(move || {
                 f(unsafe { cowns.get_mut() });
             }),
+            on_panic: Box::new(|| {}),
             count: AtomicUsize::new(requests.len() + 1),
             requests,
+            runtime: BocRuntime::current(),
+            priority: Priority::Normal,
+            #[cfg(feature = "boc-trace")]
+            id: Tracer::new_id(),
         }
     }
 
     /// Schedules the Behavior.
-    ///
-    /// Performs two phase locking (2PL) over the enqueuing of the requests.
-    /// This ensures that the overall effect of the enqueue is atomic.
     fn schedule(self) {
-        let b = Box::leak(Box::new(self));
+        Behavior::schedule_batch(vec![self]);
+    }
+
+    /// Schedules the Behaviors in order.
+    ///
+    /// Performs two phase locking (2PL) over the enqueuing of the requests of all behaviors, as if
+    /// they are one behavior. This ensures that the overall effect of the enqueue is atomic, and
+    /// the requests for a cown are enqueued in the order of the behaviors.
+    fn schedule_batch(behaviors: Vec<Behavior>) {
+        let behaviors = behaviors
+            .into_iter()
+            .map(|b| {
+                b.runtime.inner.start_behavior();
+                // should not use mem::forget
+                // Any resources the value manages, such as heap memory or a file handle,
+                // will linger forever in an unreachable state. However, it does not guarantee
+                // that pointers to this memory will remain valid.
+
+                // b should not drop here. release_one will drop it.
+                Box::into_raw(Box::new(b)).cast_const()
+            })
+            .collect::<Vec<_>>();
+        // The behaviors are not run until they are resolved below.
+        let mut requests = behaviors
+            .iter()
+            .flat_map(|&b| unsafe { &(*b).requests }.iter().map(move |r| (r, b)))
+            .collect::<Vec<_>>();
+        // The sort is stable, so that the requests for a cown are in the order of the behaviors.
+        requests.sort_by(|(r1, _), (r2, _)| r1.cmp(r2));
         unsafe {
-            for r in &b.requests {
-                r.start_enqueue(b as *const Self);
+            // The requests of the batch for a cown do not wait for each other to be scheduled.
+            for requests in requests.chunk_by(|(r1, _), (r2, _)| r1 == r2) {
+                Request::start_enqueue(requests);
             }
-            for r in &b.requests {
+            for (r, _) in &requests {
                 r.finish_enqueue();
             }
-            Behavior::resolve_one(b as *const Self);
+            // The deps are recorded here rather than while spinning in the 2PL.
+            #[cfg(feature = "boc-trace")]
+            for &b in &behaviors {
+                let b = &*b;
+                let dep = |r: &Request| Some(r.dep.load(Relaxed)).filter(|&dep| dep != usize::MAX);
+                b.runtime.inner.tracer.enqueue(
+                    b.id,
+                    b.requests
+                        .iter()
+                        .map(|r| (Arc::as_ptr(&r.target).cast::<()>().addr(), r.read))
+                        .collect(),
+                    b.requests.iter().filter_map(dep).collect(),
+                );
+            }
+            for b in behaviors {
+                Behavior::resolve_one(b);
+            }
         }
-        // should not use mem::forget
-        // Any resources the value manages, such as heap memory or a file handle,
-        // will linger forever in an unreachable state. However, it does not guarantee
-        // that pointers to this memory will remain valid.
-
-        // self should not drop here. resolve_one will drop it.
     }
 
     /// Resolves a single outstanding request for `this`.
@@ -208,18 +395,55 @@ impl Behavior {
         if tmp.count.fetch_sub(1, SeqCst) != 1 {
             return;
         }
-        // No other threads share this. It's time to destroy it.
+        // No other threads share this until the requests are released.
+        tmp.count.store(tmp.requests.len() + 1, SeqCst);
 
         let mut this = unsafe { Box::from_raw(this.cast_mut()) };
-        spawn(move || {
-            (this.thunk)();
-            for r in &this.requests {
-                unsafe {
-                    r.release();
+        let runtime = this.runtime.clone();
+        let priority = this.priority;
+        runtime.inner.scheduler.schedule_with_priority(
+            Box::new(move || {
+                // The behaviors created by the thunk run on the same runtime.
+                let runtime = this.runtime.clone();
+                let thunk = mem::replace(&mut this.thunk, Box::new(|| {}));
+                // The requests are released even if the thunk panics, so that the later behaviors
+                // on the cowns are not blocked forever. The cowns it may have left
+                // inconsistent are poisoned before they are released.
+                #[cfg(feature = "boc-trace")]
+                runtime.inner.tracer.start(this.id);
+                if panic::catch_unwind(AssertUnwindSafe(|| runtime.enter(thunk))).is_err() {
+                    for r in this.requests.iter().filter(|r| !r.read) {
+                        r.target.poison();
+                    }
+                    let on_panic = mem::replace(&mut this.on_panic, Box::new(|| {}));
+                    on_panic();
                 }
-            }
+                #[cfg(feature = "boc-trace")]
+                runtime.inner.tracer.finish(this.id);
+                let this = Box::into_raw(this);
+                for r in unsafe { &(*this).requests } {
+                    unsafe {
+                        r.release();
+                    }
+                }
+                unsafe { Behavior::release_one(this) };
+                runtime.inner.finish_behavior();
+            }),
+            priority,
+        );
+    }
+
+    /// Releases a reference to `this` held by a request or the thunk, and drops it if it is the
+    /// last one.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be a valid behavior whose thunk is started.
+    unsafe fn release_one(this: *const Self) {
+        if unsafe { (*this).count.fetch_sub(1, SeqCst) } == 1 {
             // behavior dropped here
-        });
+            drop(unsafe { Box::from_raw(this.cast_mut()) });
+        }
     }
 }
 
@@ -229,6 +453,7 @@ impl fmt::Debug for Behavior {
             .field("thunk", &"BehaviorThunk")
             .field("count", &self.count)
             .field("requests", &self.requests)
+            .field("runtime", &self.runtime)
             .finish()
     }
 }
@@ -251,6 +476,9 @@ impl Drop for Behavior {
 unsafe trait CownBase: Send {
     /// Return a pointer to the tail of this cown's request queue.
     fn last(&self) -> &AtomicPtr<Request>;
+
+    /// Marks this cown as poisoned, i.e., a behavior writing to it panicked.
+    fn poison(&self);
 }
 
 /// The value should only be accessed inside a `when!` block.
@@ -261,6 +489,8 @@ struct Cown<T: Send> {
     /// When a new node is enqueued, the enqueuer of the previous tail node will wait until the
     /// current enqueuer sets that node's `.next`.
     last: AtomicPtr<Request>,
+    /// Is a behavior writing to this cown panicked?
+    poisoned: AtomicBool,
     /// The value of this cown.
     value: UnsafeCell<T>,
 }
@@ -270,6 +500,10 @@ unsafe impl<T: Send> CownBase for Cown<T> {
     fn last(&self) -> &AtomicPtr<Request> {
         &self.last
     }
+
+    fn poison(&self) {
+        self.poisoned.store(true, SeqCst);
+    }
 }
 
 /// Public interface to Cown.
@@ -278,7 +512,7 @@ pub struct CownPtr<T: Send> {
     inner: Arc<Cown<T>>,
 }
 
-// SAFETY: In the basic version of BoC, user cannot get `&T`, so `Sync` is not necessary.
+// SAFETY: User can get `&T` only through `Read`, which requires `T: Sync`.
 unsafe impl<T: Send> Send for CownPtr<T> {}
 
 impl<T: Send> Clone for CownPtr<T> {
@@ -295,30 +529,100 @@ impl<T: Send> CownPtr<T> {
         CownPtr {
             inner: Arc::new(Cown {
                 last: AtomicPtr::new(ptr::null_mut()),
+                poisoned: AtomicBool::new(false),
                 value: UnsafeCell::new(value),
             }),
         }
     }
+
+    /// Returns `true` if a behavior writing to this cown panicked, so that the value may be
+    /// inconsistent.
+    ///
+    /// The later behaviors on a poisoned cown still run. They may check this to recover the value,
+    /// and then call [`clear_poison`](CownPtr::clear_poison).
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.poisoned.load(SeqCst)
+    }
+
+    /// Clears the poisoned state of this cown.
+    pub fn clear_poison(&self) {
+        self.inner.poisoned.store(false, SeqCst);
+    }
+
+    /// Returns a read-only request for this cown.
+    ///
+    /// Behaviors reading the same cown may run in parallel, while a behavior writing to the cown
+    /// runs exclusively.
+    pub fn read(&self) -> Read<T> {
+        Read(self.clone())
+    }
+
+    /// Returns the value if this is the only pointer to the cown and no behavior holds the cown.
+    /// Otherwise, returns `self`.
+    ///
+    /// The behaviors on the cown hold it until they are completed, e.g., after
+    /// [`BocRuntime::wait_quiescent`].
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(cown) => Ok(cown.value.into_inner()),
+            Err(inner) => Err(CownPtr { inner }),
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> CownPtr<T> {
+    /// Returns a clone of the value after the behaviors enqueued on the cown so far, by running a
+    /// behavior reading the cown on the current runtime and waiting for it.
+    ///
+    /// This blocks the current thread until the read behavior is run by the scheduler, so it
+    /// deadlocks if nothing else runs it, e.g.:
+    ///
+    /// - on a runtime with a [`DeterministicScheduler`], whose behaviors are run only by
+    ///   [`DeterministicScheduler::run`] on the calling thread, or
+    /// - inside a behavior, if the thread blocked is the one that would run the read behavior (see
+    ///   [`BehaviorHandle::wait`]), or the cown is written by the calling behavior itself.
+    pub fn get_cloned(&self) -> T {
+        run_when(self.read(), T::clone).wait()
+    }
+}
+
+/// A read-only `CownPtr`, created by [`CownPtr::read`]. The value is accessed by `&T` inside a
+/// `when!` block.
+#[derive(Debug)]
+pub struct Read<T: Send>(CownPtr<T>);
+
+impl<T: Send> Clone for Read<T> {
+    fn clone(&self) -> Self {
+        Read(self.0.clone())
+    }
 }
 
 /// Trait for a collection of `CownPtr`s.
 ///
 /// Users pass `CownPtrs` to `when!` clause to specify a collection of shared resources, and such
-/// resources can be accessed via `CownRefs` inside the thunk.
+/// resources can be accessed via `CownRefs` inside the thunk. A collection is a `CownPtr`, a
+/// `Read`, or a tuple list, a `Vec` or a boxed slice of collections, so that e.g.
+/// `(c1, (c2, (accounts, ())))` gives `(&mut A, (&mut B, (Vec<&mut C>, ())))`.
 ///
 /// # Safety
 ///
-/// `requests` should actually return the requests for the corresponding cowns.
+/// `push_requests` should actually push the requests for the corresponding cowns.
 pub unsafe trait CownPtrs {
     /// Types for references corresponding to `CownPtrs`.
     type CownRefs<'l>
     where
         Self: 'l;
 
+    /// Pushes the `Request`s for the cowns to `requests`.
+    // We push to a `Vec` to avoid allocating a collection for each part of a tuple list.
+    fn push_requests(&self, requests: &mut Vec<Request>);
+
     /// Returns a collection of `Request`.
-    // This could return a `Box<[Request]>`, but we use a `Vec` to avoid possible reallocation in
-    // the implementation.
-    fn requests(&self) -> Vec<Request>;
+    fn requests(&self) -> Vec<Request> {
+        let mut requests = Vec::new();
+        self.push_requests(&mut requests);
+        requests
+    }
 
     /// Returns mutable references of type `CownRefs`.
     ///
@@ -334,56 +638,638 @@ unsafe impl CownPtrs for () {
     where
         Self: 'l;
 
-    fn requests(&self) -> Vec<Request> {
-        Vec::new()
-    }
+    fn push_requests(&self, requests: &mut Vec<Request>) {}
 
     unsafe fn get_mut<'l>(self) -> Self::CownRefs<'l> {}
 }
 
-unsafe impl<T: Send + 'static, Ts: CownPtrs> CownPtrs for (CownPtr<T>, Ts) {
+unsafe impl<T: Send + 'static> CownPtrs for CownPtr<T> {
     type CownRefs<'l>
-        = (&'l mut T, Ts::CownRefs<'l>)
+        = &'l mut T
     where
         Self: 'l;
 
-    fn requests(&self) -> Vec<Request> {
-        let mut rs = self.1.requests();
+    fn push_requests(&self, requests: &mut Vec<Request>) {
+        let cown_base: Arc<dyn CownBase> = self.inner.clone();
+        requests.push(Request::new(cown_base, false));
+    }
+
+    unsafe fn get_mut<'l>(self) -> Self::CownRefs<'l> {
+        unsafe { &mut *self.inner.value.get() }
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> CownPtrs for Read<T> {
+    type CownRefs<'l>
+        = &'l T
+    where
+        Self: 'l;
+
+    fn push_requests(&self, requests: &mut Vec<Request>) {
         let cown_base: Arc<dyn CownBase> = self.0.inner.clone();
-        rs.push(Request::new(cown_base));
-        rs
+        requests.push(Request::new(cown_base, true));
     }
 
     unsafe fn get_mut<'l>(self) -> Self::CownRefs<'l> {
-        unsafe { (&mut *self.0.inner.value.get(), self.1.get_mut()) }
+        unsafe { &*self.0.inner.value.get() }
     }
 }
 
-unsafe impl<T: Send + 'static> CownPtrs for Vec<CownPtr<T>> {
+unsafe impl<C: CownPtrs, Cs: CownPtrs> CownPtrs for (C, Cs) {
     type CownRefs<'l>
-        = Vec<&'l mut T>
+        = (C::CownRefs<'l>, Cs::CownRefs<'l>)
     where
         Self: 'l;
 
-    fn requests(&self) -> Vec<Request> {
-        self.iter().map(|x| Request::new(x.inner.clone())).collect()
+    fn push_requests(&self, requests: &mut Vec<Request>) {
+        self.0.push_requests(requests);
+        self.1.push_requests(requests);
+    }
+
+    unsafe fn get_mut<'l>(self) -> Self::CownRefs<'l> {
+        unsafe { (self.0.get_mut(), self.1.get_mut()) }
+    }
+}
+
+unsafe impl<C: CownPtrs> CownPtrs for Vec<C> {
+    type CownRefs<'l>
+        = Vec<C::CownRefs<'l>>
+    where
+        Self: 'l;
+
+    fn push_requests(&self, requests: &mut Vec<Request>) {
+        for cowns in self {
+            cowns.push_requests(requests);
+        }
     }
 
     unsafe fn get_mut<'l>(self) -> Self::CownRefs<'l> {
-        self.iter()
-            .map(|x| unsafe { &mut *x.inner.value.get() })
+        self.into_iter()
+            .map(|cowns| unsafe { cowns.get_mut() })
             .collect()
     }
 }
 
-/// Creates a `Behavior` and schedules it. Used by "When" block.
-pub fn run_when<C, F>(cowns: C, f: F)
+unsafe impl<C: CownPtrs> CownPtrs for Box<[C]> {
+    type CownRefs<'l>
+        = Vec<C::CownRefs<'l>>
+    where
+        Self: 'l;
+
+    fn push_requests(&self, requests: &mut Vec<Request>) {
+        for cowns in self {
+            cowns.push_requests(requests);
+        }
+    }
+
+    unsafe fn get_mut<'l>(self) -> Self::CownRefs<'l> {
+        unsafe { self.into_vec().get_mut() }
+    }
+}
+
+/// The result of a behavior, shared by the behavior and its `BehaviorHandle`.
+struct Promise<R> {
+    state: Mutex<PromiseState<R>>,
+    /// Notified when the result is set.
+    ready: Condvar,
+}
+
+struct PromiseState<R> {
+    /// The result, set when the thunk finishes.
+    value: Option<R>,
+    /// Is the thunk panicked?
+    panicked: bool,
+    /// The waker of the task polling the handle, if any.
+    waker: Option<Waker>,
+}
+
+impl<R> Promise<R> {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(PromiseState {
+                value: None,
+                panicked: false,
+                waker: None,
+            }),
+            ready: Condvar::new(),
+        }
+    }
+
+    /// Sets the result, and wakes up the waiter.
+    fn fulfill(&self, value: R) {
+        self.finish(|state| state.value = Some(value));
+    }
+
+    /// Marks that the thunk panicked, and wakes up the waiter.
+    fn abandon(&self) {
+        self.finish(|state| state.panicked = true);
+    }
+
+    fn finish(&self, f: impl FnOnce(&mut PromiseState<R>)) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            f(&mut state);
+            state.waker.take()
+        };
+        self.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// A handle to the result of a behavior, returned by `run_when` and `when!`.
+///
+/// The result is retrieved by blocking on [`wait`](BehaviorHandle::wait), or by awaiting the
+/// handle. Dropping the handle does not cancel the behavior.
+pub struct BehaviorHandle<R> {
+    promise: Arc<Promise<R>>,
+}
+
+impl<R> BehaviorHandle<R> {
+    /// Blocks the current thread until the behavior finishes, and returns its result.
+    ///
+    /// This should not be called inside a behavior, which may block the thread that would run the
+    /// behavior being waited for.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the behavior panicked.
+    pub fn wait(self) -> R {
+        let mut state = self.promise.state.lock().unwrap();
+        loop {
+            if let Some(value) = state.value.take() {
+                return value;
+            }
+            assert!(!state.panicked, "the behavior panicked");
+            state = self.promise.ready.wait(state).unwrap();
+        }
+    }
+}
+
+impl<R> Future for BehaviorHandle<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut state = self.promise.state.lock().unwrap();
+        match state.value.take() {
+            Some(value) => Poll::Ready(value),
+            None if state.panicked => panic!("the behavior panicked"),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<R> fmt::Debug for BehaviorHandle<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.promise.state.lock().unwrap();
+        f.debug_struct("BehaviorHandle")
+            .field("finished", &state.value.is_some())
+            .field("panicked", &state.panicked)
+            .finish()
+    }
+}
+
+/// The priority class of a behavior.
+///
+/// When the thunks of many behaviors are ready to run, a [`Scheduler`] may run those of higher
+/// priority first. The priority does not change the order of the behaviors on a cown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Bulk work that may be delayed.
+    Low,
+    /// The priority of `run_when` and `when!`.
+    #[default]
+    Normal,
+    /// Latency-sensitive work.
+    High,
+}
+
+/// Runs the thunks of the behaviors of a [`BocRuntime`].
+pub trait Scheduler: Send + Sync {
+    /// Schedules `task` to run, e.g., on another thread.
+    fn schedule(&self, task: Box<dyn FnOnce() + Send>);
+
+    /// Schedules `task` of `priority` to run. By default, the priority is ignored.
+    fn schedule_with_priority(&self, task: Box<dyn FnOnce() + Send>, priority: Priority) {
+        let _ = priority;
+        self.schedule(task);
+    }
+}
+
+impl<S: Scheduler + ?Sized> Scheduler for Arc<S> {
+    fn schedule(&self, task: Box<dyn FnOnce() + Send>) {
+        (**self).schedule(task);
+    }
+
+    fn schedule_with_priority(&self, task: Box<dyn FnOnce() + Send>, priority: Priority) {
+        (**self).schedule_with_priority(task, priority);
+    }
+}
+
+/// A task of a `Scheduler`.
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Tasks queued by priority, and in the order they are pushed in a priority.
+struct PriorityQueue {
+    queues: Mutex<[VecDeque<Task>; 3]>,
+}
+
+impl PriorityQueue {
+    const fn new() -> Self {
+        Self {
+            queues: Mutex::new([VecDeque::new(), VecDeque::new(), VecDeque::new()]),
+        }
+    }
+
+    fn push(&self, task: Task, priority: Priority) {
+        self.queues.lock().unwrap()[priority as usize].push_back(task);
+    }
+
+    /// Pops the first task of the highest priority, if it is at least `min`.
+    fn pop(&self, min: Priority) -> Option<Task> {
+        let mut queues = self.queues.lock().unwrap();
+        queues[min as usize..]
+            .iter_mut()
+            .rev()
+            .find_map(VecDeque::pop_front)
+    }
+
+    fn len(&self) -> usize {
+        self.queues.lock().unwrap().iter().map(VecDeque::len).sum()
+    }
+}
+
+/// The tasks of `RayonScheduler` other than `Priority::Normal`. Each job spawned for them on rayon
+/// runs the first task here, if any. The jobs of the normal tasks first run the tasks of high
+/// priority here, so that they do not wait for the jobs spawned before them.
+static RAYON_TASKS: PriorityQueue = PriorityQueue::new();
+
+/// The number of tasks in `RAYON_TASKS`, so that the jobs of the normal tasks check it without
+/// locking.
+static RAYON_TASKS_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Pops a task of at least `min` priority from `RAYON_TASKS`.
+fn pop_rayon_task(min: Priority) -> Option<Task> {
+    let task = RAYON_TASKS.pop(min)?;
+    let _ = RAYON_TASKS_LEN.fetch_sub(1, Relaxed);
+    Some(task)
+}
+
+/// Runs the tasks on the global thread pool of rayon. This is the scheduler of the default runtime.
+///
+/// The tasks of higher [`Priority`] run first.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RayonScheduler;
+
+impl Scheduler for RayonScheduler {
+    fn schedule(&self, task: Box<dyn FnOnce() + Send>) {
+        self.schedule_with_priority(task, Priority::Normal);
+    }
+
+    fn schedule_with_priority(&self, task: Box<dyn FnOnce() + Send>, priority: Priority) {
+        if priority == Priority::Normal {
+            spawn(move || {
+                while RAYON_TASKS_LEN.load(Relaxed) != 0 {
+                    let Some(high) = pop_rayon_task(Priority::High) else {
+                        break;
+                    };
+                    high();
+                }
+                task();
+            });
+            return;
+        }
+        let _ = RAYON_TASKS_LEN.fetch_add(1, Relaxed);
+        RAYON_TASKS.push(task, priority);
+        // The task may have been run by the job of a normal task.
+        spawn(|| {
+            if let Some(task) = pop_rayon_task(Priority::Low) {
+                task();
+            }
+        });
+    }
+}
+
+/// Runs the tasks on the threads of the pool.
+///
+/// The pool is joined when dropped with the last runtime, unless it is dropped by a task, e.g.,
+/// when the runtime is dropped while its behaviors are running. Then the workers are detached
+/// instead. To wait for the behaviors, call [`BocRuntime::wait_quiescent`].
+impl Scheduler for ThreadPool {
+    fn schedule(&self, task: Box<dyn FnOnce() + Send>) {
+        self.execute(task);
+    }
+}
+
+/// Runs the tasks one by one on the thread calling [`run`](DeterministicScheduler::run), in the
+/// order of their [`Priority`] and then in the order they are scheduled.
+///
+/// Behaviors run in a reproducible order, which is useful for testing.
+pub struct DeterministicScheduler {
+    tasks: PriorityQueue,
+}
+
+impl Default for DeterministicScheduler {
+    fn default() -> Self {
+        Self {
+            tasks: PriorityQueue::new(),
+        }
+    }
+}
+
+impl DeterministicScheduler {
+    /// Creates a new scheduler without tasks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the scheduled tasks, including those scheduled by them, until no task is left.
+    ///
+    /// Returns the number of tasks run.
+    pub fn run(&self) -> usize {
+        let mut count = 0;
+        loop {
+            let Some(task) = self.tasks.pop(Priority::Low) else {
+                return count;
+            };
+            task();
+            count += 1;
+        }
+    }
+}
+
+impl Scheduler for DeterministicScheduler {
+    fn schedule(&self, task: Box<dyn FnOnce() + Send>) {
+        self.tasks.push(task, Priority::Normal);
+    }
+
+    fn schedule_with_priority(&self, task: Box<dyn FnOnce() + Send>, priority: Priority) {
+        self.tasks.push(task, priority);
+    }
+}
+
+impl fmt::Debug for DeterministicScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeterministicScheduler")
+            .field("tasks", &self.tasks.len())
+            .finish()
+    }
+}
+
+/// A runtime for behaviors, which runs their thunks by a [`Scheduler`].
+///
+/// `run_when` and `when!` create behaviors on the current runtime: the runtime of the behavior
+/// running on the current thread, the runtime entered by [`enter`](BocRuntime::enter), or the
+/// default runtime on rayon.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use cs431_homework::boc::{BocRuntime, CownPtr, DeterministicScheduler, run_when};
+/// use cs431_homework::{tuple_list, when};
+///
+/// let scheduler = Arc::new(DeterministicScheduler::new());
+/// let runtime = BocRuntime::new(scheduler.clone());
+/// let c1 = CownPtr::new(1);
+/// let handle = runtime.enter(|| when!(c1; g1; *g1 * 2));
+///
+/// assert_eq!(scheduler.run(), 1);
+/// assert_eq!(handle.wait(), 2);
+/// ```
+#[derive(Clone)]
+pub struct BocRuntime {
+    inner: Arc<RuntimeInner>,
+}
+
+struct RuntimeInner {
+    scheduler: Box<dyn Scheduler>,
+    /// The number of behaviors scheduled but not completed.
+    live: AtomicUsize,
+    /// Locked by `wait_quiescent` while checking `live`, and by `finish_behavior` before notifying
+    /// `quiescent`, so that the notification is not missed.
+    quiescent_lock: Mutex<()>,
+    /// Notified when `live` becomes zero.
+    quiescent: Condvar,
+    /// The records of the behaviors.
+    #[cfg(feature = "boc-trace")]
+    tracer: Tracer,
+}
+
+impl RuntimeInner {
+    /// Increments the number of live behaviors.
+    fn start_behavior(&self) {
+        let _ = self.live.fetch_add(1, Relaxed);
+    }
+
+    /// Decrements the number of live behaviors.
+    fn finish_behavior(&self) {
+        // Release synchronizes with `wait_quiescent`, so that it sees the effects of the behavior.
+        if self.live.fetch_sub(1, Release) == 1 {
+            drop(self.quiescent_lock.lock().unwrap());
+            self.quiescent.notify_all();
+        }
+    }
+}
+
+thread_local! {
+    /// The runtime of the behavior running on the current thread, or the entered runtime.
+    static CURRENT: RefCell<Option<BocRuntime>> = const { RefCell::new(None) };
+}
+
+/// The runtime used if no runtime is entered.
+static DEFAULT: LazyLock<BocRuntime> = LazyLock::new(|| BocRuntime::new(RayonScheduler));
+
+impl BocRuntime {
+    /// Creates a new runtime with `scheduler`.
+    pub fn new<S: Scheduler + 'static>(scheduler: S) -> Self {
+        Self {
+            inner: Arc::new(RuntimeInner {
+                scheduler: Box::new(scheduler),
+                live: AtomicUsize::new(0),
+                quiescent_lock: Mutex::new(()),
+                quiescent: Condvar::new(),
+                #[cfg(feature = "boc-trace")]
+                tracer: Tracer::new(),
+            }),
+        }
+    }
+
+    /// Returns the current runtime.
+    pub fn current() -> Self {
+        CURRENT
+            .with(|current| current.borrow().clone())
+            .unwrap_or_else(|| DEFAULT.clone())
+    }
+
+    /// Runs `f` with `self` as the current runtime.
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        /// Restores the previous runtime, even if `f` panics.
+        struct Restore(Option<BocRuntime>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let prev = self.0.take();
+                let _ = CURRENT.try_with(|current| current.replace(prev));
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.replace(Some(self.clone()))));
+        f()
+    }
+
+    /// Creates a `Behavior` on this runtime and schedules it. See [`run_when`].
+    pub fn run_when<C, F, R>(&self, cowns: C, f: F) -> BehaviorHandle<R>
+    where
+        C: CownPtrs + Send + 'static,
+        F: for<'l> Fn(C::CownRefs<'l>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.enter(|| run_when(cowns, f))
+    }
+
+    /// Creates a `Behavior` of `priority` on this runtime and schedules it. See
+    /// [`run_when_with_priority`].
+    pub fn run_when_with_priority<C, F, R>(
+        &self,
+        priority: Priority,
+        cowns: C,
+        f: F,
+    ) -> BehaviorHandle<R>
+    where
+        C: CownPtrs + Send + 'static,
+        F: for<'l> Fn(C::CownRefs<'l>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.enter(|| run_when_with_priority(priority, cowns, f))
+    }
+
+    /// Creates `Behavior`s on this runtime and schedules them at once. See [`run_when_batch`].
+    pub fn run_when_batch<I, C, F, R>(&self, batch: I) -> Vec<BehaviorHandle<R>>
+    where
+        I: IntoIterator<Item = (C, F)>,
+        C: CownPtrs + Send + 'static,
+        F: for<'l> Fn(C::CownRefs<'l>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.enter(|| run_when_batch(batch))
+    }
+
+    /// Blocks the current thread until all behaviors on this runtime complete, including those
+    /// created by the behaviors while waiting.
+    ///
+    /// This should not be called inside a behavior on this runtime, which never completes then.
+    pub fn wait_quiescent(&self) {
+        let mut guard = self.inner.quiescent_lock.lock().unwrap();
+        while self.inner.live.load(Acquire) != 0 {
+            guard = self.inner.quiescent.wait(guard).unwrap();
+        }
+    }
+
+    /// Takes the records of the behaviors created on this runtime so far, in the order of their
+    /// creation. See [`trace`].
+    ///
+    /// At most [`trace::CAPACITY`] records are kept, dropping the oldest ones.
+    ///
+    /// The records of the running behaviors are not updated after this is called, so this should
+    /// be called after [`wait_quiescent`](BocRuntime::wait_quiescent).
+    #[cfg(feature = "boc-trace")]
+    pub fn take_trace(&self) -> Vec<BehaviorTrace> {
+        self.inner.tracer.take()
+    }
+}
+
+impl fmt::Debug for BocRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BocRuntime")
+            .field("live", &self.inner.live.load(Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Creates a `Behavior` on the current runtime and schedules it. Used by "When" block.
+///
+/// Returns a handle to the result of `f`. If `f` panics, the cowns it writes to are poisoned (see
+/// [`CownPtr::is_poisoned`]) and released to the later behaviors.
+///
+/// # Panics
+///
+/// Panics if a cown is in `cowns` more than once, unless all of them are `Read`s.
+pub fn run_when<C, F, R>(cowns: C, f: F) -> BehaviorHandle<R>
 where
     C: CownPtrs + Send + 'static,
-    F: for<'l> Fn(C::CownRefs<'l>) + Send + 'static,
+    F: for<'l> Fn(C::CownRefs<'l>) -> R + Send + 'static,
+    R: Send + 'static,
 {
-    let b = Behavior::new(cowns, f);
+    run_when_with_priority(Priority::Normal, cowns, f)
+}
+
+/// Like [`run_when`], but the thunk is scheduled with `priority`.
+///
+/// The behavior still runs after the behaviors enqueued before it on its cowns, whatever their
+/// priorities are.
+pub fn run_when_with_priority<C, F, R>(priority: Priority, cowns: C, f: F) -> BehaviorHandle<R>
+where
+    C: CownPtrs + Send + 'static,
+    F: for<'l> Fn(C::CownRefs<'l>) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let (mut b, handle) = new_behavior(cowns, f);
+    b.priority = priority;
     b.schedule();
+    handle
+}
+
+/// Creates `Behavior`s on the current runtime and schedules them at once, in order.
+///
+/// This is equivalent to calling [`run_when`] for each element of `batch` in order, but the
+/// requests of the behaviors are sorted and enqueued together, so that a large batch of behaviors
+/// is scheduled faster.
+///
+/// Returns the handles to the results, in order.
+///
+/// The behaviors are of [`Priority::Normal`]. A behavior of another priority is created by
+/// [`run_when_with_priority`] instead.
+///
+/// # Panics
+///
+/// Panics if a cown is in the cowns of a behavior more than once, unless all of them are `Read`s.
+pub fn run_when_batch<I, C, F, R>(batch: I) -> Vec<BehaviorHandle<R>>
+where
+    I: IntoIterator<Item = (C, F)>,
+    C: CownPtrs + Send + 'static,
+    F: for<'l> Fn(C::CownRefs<'l>) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let (behaviors, handles) = batch
+        .into_iter()
+        .map(|(cowns, f)| new_behavior(cowns, f))
+        .unzip();
+    Behavior::schedule_batch(behaviors);
+    handles
+}
+
+/// Creates a `Behavior` on the current runtime running `f`, and a handle to the result.
+fn new_behavior<C, F, R>(cowns: C, f: F) -> (Behavior, BehaviorHandle<R>)
+where
+    C: CownPtrs + Send + 'static,
+    F: for<'l> Fn(C::CownRefs<'l>) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let promise = Arc::new(Promise::new());
+    let handle = BehaviorHandle {
+        promise: promise.clone(),
+    };
+    let fulfilled = promise.clone();
+    let mut b = Behavior::new(cowns, move |refs| fulfilled.fulfill(f(refs)));
+    // The waiter sees the cowns poisoned, see `Behavior::resolve_one`.
+    b.on_panic = Box::new(move || promise.abandon());
+    (b, handle)
 }
 
 /// from <https://docs.rs/tuple_list/latest/tuple_list/>
@@ -404,14 +1290,35 @@ macro_rules! tuple_list {
     ($i:expr_2021, $($e:expr_2021),*,) => ( ($i, $crate::tuple_list!($($e),*)) );
 }
 
-/// "When" block.
+/// "When" block. Returns a [`BehaviorHandle`] to the value of the thunk.
+///
+/// A cown prefixed by `read` is accessed by a shared reference, e.g.,
+/// `when!(read c1, c2; g1, g2; ...)` reads `c1` and writes to `c2`.
+///
+/// The behavior is of [`Priority::Normal`]. See [`run_when_with_priority`] for the other
+/// priorities.
 #[macro_export]
 macro_rules! when {
-    ( $( $cs:ident ),* ; $( $gs:ident ),* ; $thunk:expr_2021 ) => {{
-        run_when(tuple_list!($($cs.clone()),*), move |tuple_list!($($gs),*)| $thunk);
+    ( $( $($cs:ident)+ ),* ; $( $gs:ident ),* ; $thunk:expr_2021 ) => {{
+        run_when(
+            tuple_list!($($crate::when_cown!($($cs)+)),*),
+            move |tuple_list!($($gs),*)| $thunk,
+        )
     }};
 }
 
+/// Clones a cown in a `when!` block, as a read-only one if prefixed by `read`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! when_cown {
+    (read $c:ident) => {
+        $c.read()
+    };
+    ($c:ident) => {
+        $c.clone()
+    };
+}
+
 #[test]
 fn boc() {
     let c1 = CownPtr::new(0);
@@ -512,15 +1419,23 @@ fn boc_simple() {
     let c2_ = c2.clone();
     let c3_ = c3.clone();
 
-    when!(c1, c2; g1, g2; {
-        // c3, c2 are moved into this thunk. There's no such thing as auto-cloning move closure.
-        *g1 += 1;
-        *g2 += 1;
-        when!(c3, c2; g3, g2; {
+    let runtime = BocRuntime::new(RayonScheduler);
+    runtime.enter(|| {
+        when!(c1, c2; g1, g2; {
+            // c3, c2 are moved into this thunk. There's no such thing as auto-cloning move closure.
+            *g1 += 1;
             *g2 += 1;
-            *g3 = true;
+            when!(c3, c2; g3, g2; {
+                *g2 += 1;
+                *g3 = true;
+            });
         });
     });
+
+    // wait for termination, including the nested behavior
+    runtime.wait_quiescent();
+    assert_eq!(unsafe { *c2_.inner.value.get() }, 2);
+    assert!(unsafe { *c3_.inner.value.get() });
 }
 
 #[test]
@@ -546,15 +1461,21 @@ fn boc_channel() {
 #[test]
 fn boc_two_when_one_cown() {
     let c1 = CownPtr::new(1);
-    when!(c1; g1; {
-        *g1 += 1;
-        println!("{}", *g1);
-    });
+    let runtime = BocRuntime::new(RayonScheduler);
+    runtime.enter(|| {
+        when!(c1; g1; {
+            *g1 += 1;
+            println!("{}", *g1);
+        });
 
-    when!(c1; g1; {
-        *g1 += 1;
-        println!("{}", *g1);
+        when!(c1; g1; {
+            *g1 += 1;
+            println!("{}", *g1);
+        });
     });
+
+    runtime.wait_quiescent();
+    assert_eq!(unsafe { *c1.inner.value.get() }, 3);
 }
 
 #[test]
@@ -569,7 +1490,7 @@ fn boc_two_when_overlap_cown() {
         assert_eq!(*g1+1, *g2);
     });
 
-    println!("{}", unsafe { *c2.inner.value.get() });
+    println!("{}", c2.get_cloned());
 
     when!(c2, c3; g2, g3; {
         println!("{}", *g2);
@@ -0,0 +1,191 @@
+//! Tracing of behaviors, enabled by the `boc-trace` feature.
+//!
+//! Each [`BocRuntime`](super::BocRuntime) records the behaviors created on it: their cowns, the
+//! behaviors they wait for, and when they are enqueued, started and finished. The records are
+//! taken by [`BocRuntime::take_trace`](super::BocRuntime::take_trace), and exported by
+//! [`to_chrome_json`] (for `chrome://tracing` or Perfetto) or [`to_dot`] (for Graphviz).
+
+use core::fmt::Write;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::Relaxed;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The maximum number of records kept by a runtime. The oldest records are dropped beyond this.
+pub const CAPACITY: usize = 1 << 16;
+
+/// The id of the next behavior.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The id of the next thread running a behavior.
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD: usize = NEXT_THREAD.fetch_add(1, Relaxed);
+}
+
+/// A record of a behavior.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BehaviorTrace {
+    /// The id of the behavior, increasing in the order the behaviors are created in the process.
+    pub id: usize,
+    /// The cowns of the behavior, identified by their addresses, and whether they are read-only.
+    ///
+    /// The address of a cown may be reused after it is dropped.
+    pub cowns: Vec<(usize, bool)>,
+    /// The behaviors enqueued right before this behavior on its cowns. This behavior runs after
+    /// them, unless both read the cown.
+    pub deps: Vec<usize>,
+    /// When the behavior is enqueued, since the runtime is created.
+    pub enqueued: Duration,
+    /// When the thunk is started, since the runtime is created.
+    pub started: Option<Duration>,
+    /// When the thunk is finished, since the runtime is created.
+    pub finished: Option<Duration>,
+    /// The thread that ran the thunk, numbered in the order the threads first run a behavior.
+    pub thread: Option<usize>,
+}
+
+/// The records of the behaviors of a runtime.
+#[derive(Debug)]
+pub(crate) struct Tracer {
+    epoch: Instant,
+    behaviors: Mutex<BTreeMap<usize, BehaviorTrace>>,
+}
+
+impl Tracer {
+    pub(crate) fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            behaviors: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns a new behavior id.
+    pub(crate) fn new_id() -> usize {
+        NEXT_ID.fetch_add(1, Relaxed)
+    }
+
+    /// Records that the behavior `id` is enqueued on `cowns`, right after `deps`.
+    pub(crate) fn enqueue(&self, id: usize, cowns: Vec<(usize, bool)>, deps: Vec<usize>) {
+        let enqueued = self.epoch.elapsed();
+        let mut behaviors = self.behaviors.lock().unwrap();
+        while behaviors.len() >= CAPACITY {
+            let _ = behaviors.pop_first();
+        }
+        let _ = behaviors.insert(
+            id,
+            BehaviorTrace {
+                id,
+                cowns,
+                deps,
+                enqueued,
+                started: None,
+                finished: None,
+                thread: None,
+            },
+        );
+    }
+
+    /// Records that the thunk of the behavior `id` is started on the current thread.
+    pub(crate) fn start(&self, id: usize) {
+        let started = self.epoch.elapsed();
+        let thread = THREAD.with(|thread| *thread);
+        self.update(id, |behavior| {
+            behavior.started = Some(started);
+            behavior.thread = Some(thread);
+        });
+    }
+
+    /// Records that the thunk of the behavior `id` is finished.
+    pub(crate) fn finish(&self, id: usize) {
+        let finished = self.epoch.elapsed();
+        self.update(id, |behavior| behavior.finished = Some(finished));
+    }
+
+    /// Takes the records, in the order of the ids.
+    pub(crate) fn take(&self) -> Vec<BehaviorTrace> {
+        let behaviors = core::mem::take(&mut *self.behaviors.lock().unwrap());
+        behaviors.into_values().collect()
+    }
+
+    /// Updates the record of `id`, unless it is already taken.
+    fn update(&self, id: usize, f: impl FnOnce(&mut BehaviorTrace)) {
+        if let Some(behavior) = self.behaviors.lock().unwrap().get_mut(&id) {
+            f(behavior);
+        }
+    }
+}
+
+/// Formats the cowns of a behavior, e.g., `0x5555 w`.
+fn cown_labels(behavior: &BehaviorTrace) -> impl Iterator<Item = String> + '_ {
+    behavior
+        .cowns
+        .iter()
+        .map(|(cown, read)| format!("{cown:#x} {}", if *read { 'r' } else { 'w' }))
+}
+
+/// Exports the behaviors in the Chrome trace event format.
+///
+/// Each finished behavior is a complete event on the thread that ran it, with its cowns, deps and
+/// the time it is enqueued as arguments.
+pub fn to_chrome_json(behaviors: &[BehaviorTrace]) -> String {
+    let micros = |time: Duration| time.as_secs_f64() * 1e6;
+    let mut json = String::from("{\"traceEvents\":[");
+    let finished = behaviors.iter().filter_map(|behavior| {
+        Some((
+            behavior,
+            behavior.started?,
+            behavior.finished?,
+            behavior.thread?,
+        ))
+    });
+    for (i, (behavior, started, finished, thread)) in finished.enumerate() {
+        if i != 0 {
+            json.push(',');
+        }
+        let cowns = cown_labels(behavior)
+            .map(|cown| format!("\"{cown}\""))
+            .collect::<Vec<_>>()
+            .join(",");
+        let deps = behavior
+            .deps
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(
+            json,
+            "{{\"name\":\"behavior {}\",\"cat\":\"boc\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\
+             \"pid\":0,\"tid\":{thread},\"args\":{{\"cowns\":[{cowns}],\"deps\":[{deps}],\
+             \"enqueued\":{:.3}}}}}",
+            behavior.id,
+            micros(started),
+            micros(finished.saturating_sub(started)),
+            micros(behavior.enqueued),
+        )
+        .unwrap();
+    }
+    json.push_str("]}");
+    json
+}
+
+/// Exports the dependency DAG of the behaviors in the Graphviz DOT format.
+///
+/// Each behavior is a node labeled with its id and cowns, with edges from its deps.
+pub fn to_dot(behaviors: &[BehaviorTrace]) -> String {
+    let mut dot = String::from("digraph boc {\n");
+    for behavior in behaviors {
+        let mut label = format!("#{}", behavior.id);
+        for cown in cown_labels(behavior) {
+            write!(label, "\\n{cown}").unwrap();
+        }
+        writeln!(dot, "    b{} [label=\"{label}\"];", behavior.id).unwrap();
+        for dep in &behavior.deps {
+            writeln!(dot, "    b{dep} -> b{};", behavior.id).unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
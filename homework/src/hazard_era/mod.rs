@@ -0,0 +1,109 @@
+//! Hazard eras.
+//!
+//! A variant of hazard pointers where shields publish *eras* instead of addresses. The global era
+//! clock is advanced by `retire`, and each object records the era of its allocation (birth era)
+//! and of its retirement (retire era). A retired object is freed only if no shield publishes an
+//! era in between. As the era rarely changes, `protect` executes a fence only when it does.
+//!
+//! The API has the same shape as that of `hazard_pointer`, except that the objects must be
+//! allocated with `alloc` instead of `Box::new`, and freed with `dealloc` if they are never shared.
+//!
+//! # Example
+//!
+//! ```
+//! use std::ptr;
+//! use std::sync::atomic::{AtomicPtr, Ordering};
+//! use cs431_homework::hazard_era::{alloc, collect, retire, Shield};
+//!
+//! let shield = Shield::default();
+//! let atomic = AtomicPtr::new(alloc(1usize));
+//! let protected = shield.protect(&atomic);
+//! assert_eq!(unsafe { *protected }, 1);
+//!
+//! // unlink the block and retire
+//! atomic.store(ptr::null_mut(), Ordering::Relaxed);
+//! unsafe { retire(protected); }
+//!
+//! // manually trigger reclamation (not necessary)
+//! collect();
+//! ```
+
+use core::cell::RefCell;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+mod retire;
+mod shield;
+
+use retire::Retired;
+pub use retire::RetiredSet;
+pub use shield::Shield;
+
+use crate::hazard_pointer::HazardBag;
+
+/// Default global bag of all hazard eras. The hazard of a slot is the published era, where null
+/// means no era.
+pub static ERAS: HazardBag = HazardBag::new();
+
+/// The global era clock. Starts from 1 as the era 0 is reserved for "no era".
+static CLOCK: AtomicUsize = AtomicUsize::new(1);
+
+/// Retired objects of the exited threads, adopted by the next `collect`.
+static ORPHANS: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Default thread-local retired object list.
+    static RETIRED: RefCell<RetiredSet> = RefCell::new(RetiredSet::default());
+}
+
+/// An object with its birth era. The pointer to `data` is what the users see.
+#[repr(C)]
+struct Block<T> {
+    birth: usize,
+    data: T,
+}
+
+impl<T> Block<T> {
+    /// Returns the block of the pointer returned by `alloc`.
+    fn from_data(data: *mut T) -> *mut Self {
+        data.wrapping_byte_sub(mem::offset_of!(Self, data)).cast()
+    }
+}
+
+/// Returns the current era.
+pub fn era() -> usize {
+    CLOCK.load(Ordering::Acquire)
+}
+
+/// Allocates an object of the current era, and returns the pointer to it.
+pub fn alloc<T>(data: T) -> *mut T {
+    let block = Box::into_raw(Box::new(Block { birth: era(), data }));
+    unsafe { &raw mut (*block).data }
+}
+
+/// Frees an object allocated by `alloc`, and returns its value.
+///
+/// # Safety
+///
+/// `pointer` must be allocated by `alloc`, and must not be shared with other threads.
+pub unsafe fn dealloc<T>(pointer: *mut T) -> T {
+    unsafe { Box::from_raw(Block::from_data(pointer)) }.data
+}
+
+/// Retires an object.
+///
+/// # Safety
+///
+/// * `pointer` must be allocated by `alloc`.
+/// * `pointer` must be removed from shared memory before calling this function, and must be valid.
+/// * The same `pointer` should only be retired once.
+pub unsafe fn retire<T>(pointer: *mut T) {
+    RETIRED.with(|r| unsafe { r.borrow_mut().retire(pointer) });
+}
+
+/// Frees the objects that are `retire`d by the current thread and not `protect`ed by any other
+/// threads.
+pub fn collect() {
+    RETIRED.with(|r| r.borrow_mut().collect());
+}
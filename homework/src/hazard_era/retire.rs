@@ -0,0 +1,197 @@
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+use std::collections::HashSet;
+
+use super::{Block, CLOCK, ERAS, ORPHANS, era};
+use crate::hazard_pointer::HazardBag;
+
+/// A retired object.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Retired {
+    /// The machine representation of the pointer to the `Block`.
+    block: *mut (),
+    /// The function pointer to `free::<T>` where `T` is the type of the object.
+    free: unsafe fn(*mut ()),
+    birth: usize,
+    retire: usize,
+}
+
+// SAFETY: The retired objects are freed in other threads only if they are orphaned, for which the
+// caller of `RetiredSet::retire` is responsible.
+unsafe impl Send for Retired {}
+
+impl Retired {
+    /// Returns `true` if the object may be reachable at one of `eras`.
+    fn is_protected(&self, eras: &HashSet<usize>) -> bool {
+        eras.iter()
+            .any(|era| (self.birth..=self.retire).contains(era))
+    }
+}
+
+/// Frees the objects in `retired` that are not protected by the eras of `hazards`.
+fn reclaim(hazards: &HazardBag, retired: &mut Vec<Retired>) {
+    let eras = hazards
+        .all_hazards()
+        .into_iter()
+        .map(|era| era.addr())
+        .collect::<HashSet<_>>();
+    retired.retain(|retired| {
+        if retired.is_protected(&eras) {
+            return true;
+        }
+        unsafe { (retired.free)(retired.block) };
+        false
+    });
+}
+
+/// Thread-local list of retired objects.
+#[derive(Debug)]
+pub struct RetiredSet {
+    inner: Vec<Retired>,
+    _marker: PhantomData<*const ()>, // !Send + !Sync
+}
+
+impl RetiredSet {
+    /// The max length of retired object list.
+    pub(crate) const THRESHOLD: usize = 64;
+
+    /// Create a new retired object list.
+    pub fn new() -> Self {
+        Self {
+            inner: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Retires an object, and advances the era clock if the object is born in the current era.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be allocated by `alloc`.
+    /// * `pointer` must be removed from shared memory before calling this function, and must be
+    ///   valid.
+    /// * The same `pointer` should only be retired once.
+    /// * `pointer` must be safe to free in another thread, as the objects remaining at the exit of
+    ///   the current thread are freed by the other threads.
+    pub unsafe fn retire<T>(&mut self, pointer: *mut T) {
+        /// Frees a block. This function is defined here as we know about the type of the object
+        /// only at the time of retiring it.
+        ///
+        /// # Safety
+        ///
+        /// Subsumes the safety requirements of `dealloc`.
+        unsafe fn free<T>(block: *mut ()) {
+            drop(unsafe { Box::from_raw(block.cast::<Block<T>>()) })
+        }
+
+        let block = Block::from_data(pointer);
+        let birth = unsafe { (*block).birth };
+        let retire = era();
+        // Otherwise, the objects allocated from now on would be blocked by the shields of the
+        // current era until they are retired.
+        if birth == retire {
+            let _ = CLOCK.compare_exchange(retire, retire + 1, Ordering::AcqRel, Ordering::Relaxed);
+        }
+        self.inner.push(Retired {
+            block: block.cast(),
+            free: free::<T>,
+            birth,
+            retire,
+        });
+        if self.inner.len() >= Self::THRESHOLD {
+            self.collect();
+        }
+    }
+
+    /// Free the objects that are `retire`d by the current thread and not `protect`ed by any other
+    /// threads.
+    ///
+    /// The objects orphaned by the exited threads are also adopted and freed.
+    pub fn collect(&mut self) {
+        if let Ok(mut orphans) = ORPHANS.try_lock() {
+            self.inner.append(&mut orphans);
+        }
+        reclaim(&ERAS, &mut self.inner);
+    }
+}
+
+impl Default for RetiredSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for RetiredSet {
+    fn drop(&mut self) {
+        // The remaining objects are handed off to the other threads, as in `hazard_pointer`.
+        self.collect();
+        if !self.inner.is_empty() {
+            ORPHANS.lock().unwrap().append(&mut self.inner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicPtr;
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::thread;
+
+    use super::super::{Shield, alloc, era};
+    use super::RetiredSet;
+
+    struct Tester(Arc<Mutex<HashSet<usize>>>, usize);
+    impl Drop for Tester {
+        fn drop(&mut self) {
+            let _ = self.0.lock().unwrap().insert(self.1);
+        }
+    }
+
+    // A shield should protect the objects reachable at its era, but not those born after it.
+    #[test]
+    fn protect_era() {
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        let mut retired = RetiredSet::new();
+
+        let old = alloc(Tester(freed.clone(), 0));
+        let src = AtomicPtr::new(old);
+        let (protected, unprotected) = (Barrier::new(2), Barrier::new(2));
+        thread::scope(|s| {
+            let _ = s.spawn(|| {
+                let shield = Shield::default();
+                let _ = shield.protect(&src);
+                let _ = protected.wait();
+                let _ = unprotected.wait();
+            });
+            let _ = protected.wait();
+
+            // born at or before the era of the shield
+            unsafe { retired.retire(old) };
+            retired.collect();
+            assert!(!freed.lock().unwrap().contains(&0));
+
+            // born after the era of the shield, as the clock is advanced by retiring an object of
+            // the current era
+            unsafe { retired.retire(alloc(())) };
+            let new = alloc(Tester(freed.clone(), 1));
+            unsafe { retired.retire(new) };
+            retired.collect();
+            assert!(freed.lock().unwrap().contains(&1));
+
+            let _ = unprotected.wait();
+        });
+
+        retired.collect();
+        assert!(freed.lock().unwrap().contains(&0));
+    }
+
+    // Retiring objects born in the current era should advance the clock.
+    #[test]
+    fn advance_era() {
+        let mut retired = RetiredSet::new();
+        let before = era();
+        unsafe { retired.retire(alloc(0usize)) };
+        assert!(era() > before);
+    }
+}
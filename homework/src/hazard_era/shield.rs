@@ -0,0 +1,80 @@
+use core::cell::Cell;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering, fence};
+
+use super::{ERAS, era};
+use crate::hazard_pointer;
+
+/// Represents the ownership of a hazard era slot.
+///
+/// A shield protects all the objects that are reachable at some point of its published era, i.e.,
+/// those born at or before the era and retired at or after it.
+#[derive(Debug)]
+pub struct Shield {
+    inner: hazard_pointer::Shield,
+    /// The era published to the slot, or 0 if none.
+    era: Cell<usize>,
+}
+
+impl Shield {
+    /// Creates a new shield for hazard era.
+    pub fn new() -> Self {
+        Self {
+            inner: hazard_pointer::Shield::new(&ERAS),
+            era: Cell::new(0),
+        }
+    }
+
+    /// Publishes `era` to the slot, unless it is already published.
+    fn publish(&self, era: usize) {
+        if self.era.replace(era) != era {
+            self.inner.set(ptr::without_provenance_mut::<()>(era));
+            // Makes the era visible to `all_hazards` of the reclaimers before re-reading `src`.
+            fence(Ordering::SeqCst);
+        }
+    }
+
+    /// Clear the hazard era slot.
+    pub fn clear(&self) {
+        self.era.set(0);
+        self.inner.clear();
+    }
+
+    /// Try protecting `pointer` obtained from `src`. If not, returns the current value.
+    ///
+    /// If "`src` still pointing to `pointer`" implies that `pointer` is not retired, then `Ok(())`
+    /// means that this shield is validated.
+    pub fn try_protect<T>(&self, pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), *mut T> {
+        let era = era();
+        self.publish(era);
+        let current = src.load(Ordering::Acquire);
+        // `pointer` is reachable while the era is `era` only if the clock is not advanced.
+        if current == pointer && super::era() == era {
+            Ok(())
+        } else {
+            self.clear();
+            Err(current)
+        }
+    }
+
+    /// Get a protected pointer from `src`.
+    ///
+    /// Unlike hazard pointers, no fence is executed if the era is unchanged since the last
+    /// `protect`.
+    pub fn protect<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        loop {
+            let pointer = src.load(Ordering::Acquire);
+            let era = era();
+            if era == self.era.get() {
+                return pointer;
+            }
+            self.publish(era);
+        }
+    }
+}
+
+impl Default for Shield {
+    fn default() -> Self {
+        Self::new()
+    }
+}
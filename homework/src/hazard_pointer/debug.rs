@@ -0,0 +1,255 @@
+//! Runtime checks of the usage of hazard pointers, enabled by the `check-hazard` feature.
+//!
+//! * Retiring a pointer that is already retired and not yet freed panics.
+//! * Retiring a pointer that is protected by a shield of the current thread is reported once per
+//!   thread. It is not freed until the shield is cleared, which may be unintended.
+//! * `outstanding_shields` and `pending_retired` count the shields alive in each thread and the
+//!   retired pointers that are not freed yet, by the threads that retired them.
+//! * The shields alive at the exit of a thread are reported as leaked. At the exit of the main
+//!   thread, i.e., of the process, all the outstanding shields and pending retired pointers are
+//!   reported by `report`. Only the threads that have used hazard pointers are checked.
+
+use core::cell::{Cell, RefCell};
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+/// Addresses of all retired pointers that are not freed yet, with the threads that retired them.
+static RETIRED_POINTERS: Mutex<BTreeMap<usize, ThreadId>> = Mutex::new(BTreeMap::new());
+
+/// The number of the shields alive in each thread that has created one and has not exited.
+static SHIELD_COUNTS: Mutex<Vec<(ThreadId, usize)>> = Mutex::new(Vec::new());
+
+std::thread_local! {
+    /// Hazards of the shields alive in the current thread.
+    static SHIELDS: RefCell<Vec<*const AtomicPtr<()>>> = const { RefCell::new(Vec::new()) };
+
+    /// Whether retiring a pointer protected by the current thread has been reported.
+    static REPORTED: Cell<bool> = const { Cell::new(false) };
+
+    /// Checks the leaks at the exit of the current thread.
+    static EXIT_CHECK: ExitCheck = ExitCheck {
+        thread: thread::current().id(),
+        main: thread::current().name() == Some("main"),
+    };
+}
+
+/// Reports the shields of `thread` alive at its exit, and if it is the main thread, all the leaks
+/// at the exit of the process.
+struct ExitCheck {
+    thread: ThreadId,
+    main: bool,
+}
+
+impl Drop for ExitCheck {
+    fn drop(&mut self) {
+        let count = {
+            let mut counts = SHIELD_COUNTS.lock().unwrap();
+            counts
+                .iter()
+                .position(|(thread, _)| *thread == self.thread)
+                .map_or(0, |index| counts.swap_remove(index).1)
+        };
+        if count != 0 {
+            eprintln!(
+                "hazard pointer: {count} shield(s) leaked by {:?}",
+                self.thread
+            );
+        }
+        if self.main {
+            let _ = report();
+        }
+    }
+}
+
+/// Updates the number of the shields alive in the current thread.
+fn set_shield_count(count: usize) {
+    let Ok(id) = EXIT_CHECK.try_with(|check| check.thread) else {
+        return;
+    };
+    let mut counts = SHIELD_COUNTS.lock().unwrap();
+    match counts.iter().position(|(thread, _)| *thread == id) {
+        Some(index) => counts[index].1 = count,
+        None => counts.push((id, count)),
+    }
+}
+
+/// Registers a shield of the current thread with its `hazard`.
+pub(crate) fn on_shield_new(hazard: *const AtomicPtr<()>) {
+    if let Ok(count) = SHIELDS.try_with(|shields| {
+        let mut shields = shields.borrow_mut();
+        shields.push(hazard);
+        shields.len()
+    }) {
+        set_shield_count(count);
+    }
+}
+
+/// Deregisters a shield of the current thread with its `hazard`.
+pub(crate) fn on_shield_drop(hazard: *const AtomicPtr<()>) {
+    if let Ok(count) = SHIELDS.try_with(|shields| {
+        let mut shields = shields.borrow_mut();
+        if let Some(index) = shields.iter().position(|&h| h == hazard) {
+            let _ = shields.swap_remove(index);
+        }
+        shields.len()
+    }) {
+        set_shield_count(count);
+    }
+}
+
+/// Checks the retirement of `pointer`.
+///
+/// # Panics
+///
+/// Panics if `pointer` is already retired and not freed yet.
+pub(crate) fn on_retire(pointer: *mut ()) {
+    // Compared without masking the tags, as `reclaim` does: a tagged hazard does not protect the
+    // pointer.
+    let protected = SHIELDS
+        .try_with(|shields| {
+            shields.borrow().iter().any(|&hazard| {
+                // SAFETY: the slots of alive shields are not freed.
+                unsafe { &*hazard }.load(Ordering::Relaxed) == pointer
+            })
+        })
+        .unwrap_or(false);
+    if protected && !REPORTED.replace(true) {
+        eprintln!(
+            "hazard pointer: retiring {pointer:p} protected by a shield of {:?}",
+            thread::current().id()
+        );
+    }
+
+    let id = EXIT_CHECK
+        .try_with(|check| check.thread)
+        .unwrap_or_else(|_| thread::current().id());
+    assert!(
+        RETIRED_POINTERS
+            .lock()
+            .unwrap()
+            .insert(pointer.addr(), id)
+            .is_none(),
+        "hazard pointer: {pointer:p} is retired twice"
+    );
+}
+
+/// Records that the retired `pointer` is freed.
+pub(crate) fn on_free(pointer: *mut ()) {
+    let _ = RETIRED_POINTERS.lock().unwrap().remove(&pointer.addr());
+}
+
+/// Returns the number of the shields alive in each thread that has any. The shields of the exited
+/// threads are not counted.
+pub fn outstanding_shields() -> HashMap<ThreadId, usize> {
+    SHIELD_COUNTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, count)| *count != 0)
+        .copied()
+        .collect()
+}
+
+/// Returns the number of the pointers retired and not freed yet, by the threads that retired
+/// them.
+pub fn pending_retired() -> HashMap<ThreadId, usize> {
+    let mut pending = HashMap::new();
+    for thread in RETIRED_POINTERS.lock().unwrap().values() {
+        *pending.entry(*thread).or_default() += 1;
+    }
+    pending
+}
+
+/// Prints the outstanding shields and the pending retired pointers of all threads to stderr.
+/// Returns `true` if there are none of them.
+pub fn report() -> bool {
+    let shields = outstanding_shields();
+    for (thread, count) in &shields {
+        eprintln!("hazard pointer: {count} shield(s) alive in {thread:?}");
+    }
+    let retired = RETIRED_POINTERS.lock().unwrap();
+    if !retired.is_empty() {
+        eprintln!(
+            "hazard pointer: {} retired pointer(s) not freed: {:x?}",
+            retired.len(),
+            retired.keys().collect::<Vec<_>>()
+        );
+    }
+    shields.is_empty() && retired.is_empty()
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::sync::mpsc;
+    use std::{mem, thread};
+
+    use super::{outstanding_shields, pending_retired, report};
+    use crate::hazard_pointer::{HazardBag, RetiredSet, Shield};
+
+    #[test]
+    #[should_panic(expected = "retired twice")]
+    fn double_retire() {
+        let hazards = HazardBag::new();
+        let mut retires = RetiredSet::new(&hazards);
+        let pointer = Box::into_raw(Box::new(0usize));
+        unsafe { retires.retire(pointer) };
+        unsafe { retires.retire(pointer) };
+    }
+
+    // The shields should be counted per thread, and visible to the other threads.
+    #[test]
+    fn count_shields() {
+        let hazards = HazardBag::new();
+        let (created, check) = mpsc::channel();
+        let (checked, drop_shields) = mpsc::channel();
+        thread::scope(|s| {
+            let hazards = &hazards;
+            let handle = s.spawn(move || {
+                let shields = (0..4).map(|_| Shield::new(hazards)).collect::<Vec<_>>();
+                created.send(()).unwrap();
+                drop_shields.recv().unwrap();
+                drop(shields);
+                assert_eq!(outstanding_shields().get(&thread::current().id()), None);
+                // leaked at the exit of the thread
+                mem::forget(Shield::new(hazards));
+            });
+            check.recv().unwrap();
+            let id = handle.thread().id();
+            assert_eq!(outstanding_shields().get(&id), Some(&4));
+            assert!(!report());
+            checked.send(()).unwrap();
+            handle.join().unwrap();
+            assert_eq!(outstanding_shields().get(&id), None);
+        });
+    }
+
+    #[test]
+    fn pending() {
+        thread::spawn(|| {
+            let id = thread::current().id();
+            let hazards = HazardBag::new();
+            let shield = Shield::new(&hazards);
+            let pointer = Box::into_raw(Box::new(0usize));
+            shield.set(pointer);
+
+            let mut retires = RetiredSet::new(&hazards);
+            unsafe { retires.retire(pointer) };
+            unsafe { retires.retire(Box::into_raw(Box::new(1usize))) };
+            assert_eq!(pending_retired().get(&id), Some(&2));
+            retires.collect();
+            assert_eq!(pending_retired().get(&id), Some(&1));
+
+            drop(shield);
+            retires.collect();
+            assert_eq!(pending_retired().get(&id), None);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,328 @@
+use core::mem;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Mutex;
+#[cfg(not(feature = "check-loom"))]
+use std::thread::{self, ThreadId};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::Mutex;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::thread::{self, ThreadId};
+
+use super::retire::{Retired, dispose, new_retired, reclaim, threshold};
+use super::{HazardBag, RetiredSet, Shield};
+
+/// Hazard pointer domain.
+///
+/// A domain owns its own `HazardBag` and retired pointer lists, so that the reclamation of a data
+/// structure does not scan the hazards of the others, and all of its retired pointers are freed
+/// when the domain is dropped. The global `HAZARDS` and `retire`/`collect` form the default
+/// domain.
+///
+/// All the `Shield`s created by `shield()` must be dropped before the domain.
+///
+/// # Example
+///
+/// ```
+/// use std::ptr;
+/// use std::sync::atomic::{AtomicPtr, Ordering};
+/// use cs431_homework::hazard_pointer::Domain;
+///
+/// let domain = Domain::new();
+/// let shield = domain.shield();
+/// let atomic = AtomicPtr::new(Box::leak(Box::new(1usize)));
+/// let protected = shield.protect(&atomic);
+/// assert_eq!(unsafe { *protected }, 1);
+///
+/// atomic.store(ptr::null_mut(), Ordering::Relaxed);
+/// unsafe { domain.retire(protected) };
+/// drop(shield);
+///
+/// // the remaining retired pointers are freed when the domain is dropped
+/// drop(domain);
+/// ```
+#[derive(Debug)]
+pub struct Domain {
+    hazards: HazardBag,
+    /// Retired pointer lists of the threads that retired to this domain. A list is taken out while
+    /// its thread reclaims it.
+    retired: Mutex<Vec<(ThreadId, Vec<Retired>)>>,
+    /// The ratio of `threshold` to the number of active hazard slots. See `RetiredSet`.
+    factor: usize,
+    /// The length of a retired pointer list that triggers reclamation. See `RetiredSet`.
+    threshold: AtomicUsize,
+}
+
+// SAFETY: Retired pointers are `Send` (see `retire`), and they are accessed only with `retired`
+// locked, or after taken out of it.
+unsafe impl Send for Domain {}
+unsafe impl Sync for Domain {}
+
+impl Domain {
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new domain.
+    pub const fn new() -> Self {
+        Self::with_factor(RetiredSet::DEFAULT_FACTOR)
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new domain.
+    pub fn new() -> Self {
+        Self::with_factor(RetiredSet::DEFAULT_FACTOR)
+    }
+
+    #[cfg(not(feature = "check-loom"))]
+    /// Creates a new domain, whose retired pointer lists are collected when their length reaches
+    /// `factor` times the number of active hazard slots. See `RetiredSet::with_factor`.
+    pub const fn with_factor(factor: usize) -> Self {
+        Self {
+            hazards: HazardBag::new(),
+            retired: Mutex::new(Vec::new()),
+            factor,
+            threshold: AtomicUsize::new(RetiredSet::THRESHOLD),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    /// Creates a new domain, whose retired pointer lists are collected when their length reaches
+    /// `factor` times the number of active hazard slots. See `RetiredSet::with_factor`.
+    pub fn with_factor(factor: usize) -> Self {
+        Self {
+            hazards: HazardBag::new(),
+            retired: Mutex::new(Vec::new()),
+            factor,
+            threshold: AtomicUsize::new(RetiredSet::THRESHOLD),
+        }
+    }
+
+    /// Returns the hazard bag of this domain.
+    pub fn hazards(&self) -> &HazardBag {
+        &self.hazards
+    }
+
+    /// Creates a new shield in this domain.
+    pub fn shield(&self) -> Shield {
+        Shield::new(&self.hazards)
+    }
+
+    /// Retires a pointer to the current thread's list of this domain.
+    ///
+    /// # Safety
+    ///
+    /// * `pointer` must be removed from shared memory before calling this function, and must be
+    ///   valid.
+    /// * The same `pointer` should only be retired once.
+    /// * `pointer` must be protected only by the shields of this domain.
+    pub unsafe fn retire<T: Send>(&self, pointer: *mut T) {
+        let id = thread::current().id();
+        let local = {
+            let mut retired = self.retired.lock().unwrap();
+            let local = Self::local(&mut retired, id);
+            local.push(new_retired(pointer));
+            (local.len() >= self.threshold.load(Ordering::Relaxed)).then(|| mem::take(local))
+        };
+        self.hazards.counters().on_retire();
+        if let Some(local) = local {
+            self.reclaim_local(id, local);
+            self.update_threshold();
+        }
+    }
+
+    /// Frees the pointers that are `retire`d to this domain by the current thread and not
+    /// `protect`ed by any shields of this domain.
+    pub fn collect(&self) {
+        let id = thread::current().id();
+        let local = {
+            let mut retired = self.retired.lock().unwrap();
+            let local = retired
+                .iter_mut()
+                .find(|(owner, _)| *owner == id)
+                .map(|(_, local)| mem::take(local));
+            // Lists of the threads that have nothing left are removed, so that exited threads do
+            // not pile up.
+            retired.retain(|(_, local)| !local.is_empty());
+            local
+        };
+        if let Some(local) = local {
+            self.reclaim_local(id, local);
+        }
+        self.update_threshold();
+    }
+
+    /// Returns the list of the thread `id`, adding an empty one if there is none.
+    fn local(retired: &mut Vec<(ThreadId, Vec<Retired>)>, id: ThreadId) -> &mut Vec<Retired> {
+        match retired.iter().position(|(owner, _)| *owner == id) {
+            Some(index) => &mut retired[index].1,
+            None => {
+                retired.push((id, Vec::new()));
+                &mut retired.last_mut().unwrap().1
+            }
+        }
+    }
+
+    /// Frees the unprotected pointers of `local`, which is taken out of the list of the thread
+    /// `id`, and puts the rest back. The pointers are freed without locking `retired`, so that the
+    /// other threads are not blocked by the destructors, which may also retire to this domain.
+    fn reclaim_local(&self, id: ThreadId, mut local: Vec<Retired>) {
+        let _ = reclaim(&self.hazards, &mut local);
+        if !local.is_empty() {
+            let mut retired = self.retired.lock().unwrap();
+            Self::local(&mut retired, id).append(&mut local);
+        }
+    }
+
+    fn update_threshold(&self) {
+        let threshold = threshold(&self.hazards, self.factor);
+        self.threshold.store(threshold, Ordering::Relaxed);
+    }
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Domain {
+    /// Frees all the retired pointers of all threads.
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let retired = mem::take(self.retired.get_mut().unwrap());
+        #[cfg(feature = "check-loom")]
+        let retired = mem::take(&mut *self.retired.lock().unwrap());
+
+        for (_, local) in retired {
+            for retired in local {
+                unsafe { dispose(retired) };
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicPtr;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::Domain;
+
+    struct Tester(Arc<Mutex<HashSet<usize>>>, usize);
+    impl Drop for Tester {
+        fn drop(&mut self) {
+            let _ = self.0.lock().unwrap().insert(self.1);
+        }
+    }
+
+    // The hazards of a domain should not block the reclamation of the other domains.
+    #[test]
+    fn independent_domains() {
+        let domain1 = Domain::new();
+        let domain2 = Domain::new();
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+
+        let pointer = Box::into_raw(Box::new(Tester(freed.clone(), 0)));
+        let src = AtomicPtr::new(pointer);
+        let shield = domain1.shield();
+        let _ = shield.protect(&src);
+
+        unsafe { domain2.retire(pointer) };
+        domain2.collect();
+        assert!(freed.lock().unwrap().contains(&0));
+
+        let pointer = Box::into_raw(Box::new(Tester(freed.clone(), 1)));
+        src.store(pointer, core::sync::atomic::Ordering::Relaxed);
+        let _ = shield.protect(&src);
+        unsafe { domain1.retire(pointer) };
+        domain1.collect();
+        assert!(!freed.lock().unwrap().contains(&1));
+
+        drop(shield);
+        domain1.collect();
+        assert!(freed.lock().unwrap().contains(&1));
+    }
+
+    // The threshold should be `factor` times the number of active slots of the domain.
+    #[test]
+    fn threshold_factor() {
+        const FACTOR: usize = 4;
+        const SLOTS: usize = 100;
+
+        let domain = Domain::with_factor(FACTOR);
+        let shields = (0..SLOTS).map(|_| domain.shield()).collect::<Vec<_>>();
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        // the first collection updates the threshold
+        domain.collect();
+        for i in 0..FACTOR * SLOTS - 1 {
+            unsafe { domain.retire(Box::into_raw(Box::new(Tester(freed.clone(), i)))) };
+        }
+        assert!(freed.lock().unwrap().is_empty());
+        let last = FACTOR * SLOTS - 1;
+        unsafe { domain.retire(Box::into_raw(Box::new(Tester(freed.clone(), last)))) };
+        assert_eq!(
+            *freed.lock().unwrap(),
+            (0..FACTOR * SLOTS).collect::<HashSet<_>>()
+        );
+        drop(shields);
+    }
+
+    // A destructor should be able to retire to the domain that frees it.
+    #[test]
+    fn retire_in_destructor() {
+        static DOMAIN: Domain = Domain::new();
+
+        struct Retirer(Arc<Mutex<HashSet<usize>>>);
+        impl Drop for Retirer {
+            fn drop(&mut self) {
+                let pointer = Box::into_raw(Box::new(Tester(self.0.clone(), 0)));
+                unsafe { DOMAIN.retire(pointer) };
+            }
+        }
+
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        unsafe { DOMAIN.retire(Box::into_raw(Box::new(Retirer(freed.clone())))) };
+        DOMAIN.collect();
+        assert!(freed.lock().unwrap().is_empty());
+        DOMAIN.collect();
+        assert!(freed.lock().unwrap().contains(&0));
+    }
+
+    // Dropping a domain should free all the pointers retired by all threads.
+    #[test]
+    fn drop_domain() {
+        const THREADS: usize = 8;
+        const VALUES: usize = 16;
+
+        let domain = Domain::new();
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let domain = &domain;
+                let freed = &freed;
+                let _ = s.spawn(move || {
+                    // keep everything protected while collecting, so that nothing is freed
+                    let shields = (0..VALUES).map(|_| domain.shield()).collect::<Vec<_>>();
+                    for (i, shield) in shields.iter().enumerate() {
+                        let pointer =
+                            Box::into_raw(Box::new(Tester(freed.clone(), t * VALUES + i)));
+                        shield.set(pointer);
+                        unsafe { domain.retire(pointer) };
+                    }
+                    domain.collect();
+                });
+            }
+        });
+        assert!(freed.lock().unwrap().is_empty());
+
+        drop(domain);
+        assert_eq!(
+            *freed.lock().unwrap(),
+            (0..THREADS * VALUES).collect::<HashSet<_>>()
+        );
+    }
+}
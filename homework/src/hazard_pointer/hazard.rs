@@ -1,29 +1,86 @@
+#[cfg(not(feature = "check-loom"))]
+use core::cell::RefCell;
+use core::mem;
 use core::ptr::{self, NonNull};
 #[cfg(not(feature = "check-loom"))]
 use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering, fence};
 use std::collections::HashSet;
 use std::fmt;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Mutex;
+#[cfg(not(feature = "check-loom"))]
+use std::thread::LocalKey;
 
+#[cfg(feature = "check-loom")]
+use loom::sync::Mutex;
 #[cfg(feature = "check-loom")]
 use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering, fence};
 
 use super::HAZARDS;
+#[cfg(not(feature = "check-loom"))]
+use super::SLOTS;
+use super::retire::{Retired, dispose};
+use super::stats::{Counters, Stats};
 
 /// Represents the ownership of a hazard pointer slot.
 pub struct Shield {
     slot: NonNull<HazardSlot>,
+    // The thread-local cache to return the slot to, e.g., `SLOTS` for the slots of `HAZARDS`.
+    #[cfg(not(feature = "check-loom"))]
+    cache: Option<&'static LocalKey<RefCell<SlotCache>>>,
 }
 
 impl Shield {
     /// Creates a new shield for hazard pointer.
+    ///
+    /// Shields of the global `HAZARDS` first reuse the slots cached by the current thread, so that
+    /// creating and dropping them does not scan the bag.
     pub fn new(hazards: &HazardBag) -> Self {
-        let slot = hazards.acquire_slot().into();
-        Self { slot }
+        // The cache would outlive the per-execution `HAZARDS` of loom, so it is disabled there.
+        #[cfg(not(feature = "check-loom"))]
+        if ptr::eq(hazards, &HAZARDS) {
+            // SAFETY: `SLOTS` caches only the slots of `HAZARDS`.
+            return unsafe { Self::with_cache(hazards, &SLOTS) };
+        }
+
+        Self::from_slot(hazards.acquire_slot().into())
+    }
+
+    /// Creates a new shield, reusing a slot cached in `cache` if any. The slot is returned to
+    /// `cache` when the shield is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `cache` must cache only the slots of `hazards`.
+    #[cfg(not(feature = "check-loom"))]
+    unsafe fn with_cache(
+        hazards: &HazardBag,
+        cache: &'static LocalKey<RefCell<SlotCache>>,
+    ) -> Self {
+        let slot = cache
+            .try_with(|slots| slots.borrow_mut().pop())
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| hazards.acquire_slot().into());
+        let mut shield = Self::from_slot(slot);
+        shield.cache = Some(cache);
+        shield
+    }
+
+    fn from_slot(slot: NonNull<HazardSlot>) -> Self {
+        #[cfg(feature = "check-hazard")]
+        super::debug::on_shield_new(&unsafe { slot.as_ref() }.hazard);
+        Self {
+            slot,
+            #[cfg(not(feature = "check-loom"))]
+            cache: None,
+        }
     }
 
     /// Store `pointer` to the hazard slot.
     pub fn set<T>(&self, pointer: *mut T) {
-        todo!()
+        let slot = unsafe { self.slot.as_ref() };
+        slot.hazard.store(pointer.cast(), Ordering::Release);
     }
 
     /// Clear the hazard slot.
@@ -36,7 +93,14 @@ impl Shield {
     /// For a pointer `p`, if "`src` still pointing to `pointer`" implies that `p` is not retired,
     /// then `Ok(())` means that shields set to `p` are validated.
     pub fn validate<T>(pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), *mut T> {
-        todo!()
+        // Makes the hazard visible to `all_hazards` of the reclaimers before re-reading `src`.
+        fence(Ordering::SeqCst);
+        let current = src.load(Ordering::Acquire);
+        if current == pointer {
+            Ok(())
+        } else {
+            Err(current)
+        }
     }
 
     /// Try protecting `pointer` obtained from `src`. If not, returns the current value.
@@ -60,6 +124,69 @@ impl Shield {
         }
         pointer
     }
+
+    /// Like `validate()`, but `pointer` and the value of `src` are compared with their tags masked.
+    /// If not, returns the current (tagged) value.
+    ///
+    /// Note that `Ok(())` does not mean that the tag of `src` is unchanged. If the tag of `src`
+    /// tells whether it still points to `pointer` (e.g., a marked `next` of a removed node in
+    /// Harris-Michael list), the caller should check it again.
+    pub fn validate_tagged<T>(pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), *mut T> {
+        fence(Ordering::SeqCst);
+        let current = src.load(Ordering::Acquire);
+        if untagged(current) == untagged(pointer) {
+            Ok(())
+        } else {
+            Err(current)
+        }
+    }
+
+    /// Like `try_protect()`, but for a tagged `pointer`. The untagged `pointer` is stored to the
+    /// hazard slot, and validated by `validate_tagged()`.
+    pub fn try_protect_tagged<T>(&self, pointer: *mut T, src: &AtomicPtr<T>) -> Result<(), *mut T> {
+        self.set(untagged(pointer));
+        Self::validate_tagged(pointer, src).inspect_err(|_| self.clear())
+    }
+
+    /// Get a protected tagged pointer from `src`. The returned pointer keeps its tag, which is one
+    /// of the tags that `src` had while the untagged pointer is protected.
+    ///
+    /// See `try_protect_tagged()`.
+    pub fn protect_tagged<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        let mut pointer = src.load(Ordering::Relaxed);
+        while let Err(new) = self.try_protect_tagged(pointer, src) {
+            pointer = new;
+            #[cfg(feature = "check-loom")]
+            loom::sync::atomic::spin_loop_hint();
+        }
+        pointer
+    }
+}
+
+/// Returns the mask of the tag bits of pointers to `T`, i.e., the low bits that are unused due to
+/// the alignment of `T`.
+#[inline]
+const fn tag_mask<T>() -> usize {
+    mem::align_of::<T>() - 1
+}
+
+/// Returns `pointer` with its tag bits cleared.
+#[inline]
+pub fn untagged<T>(pointer: *mut T) -> *mut T {
+    pointer.map_addr(|addr| addr & !tag_mask::<T>())
+}
+
+/// Returns the tag of `pointer`.
+#[inline]
+pub fn tag<T>(pointer: *mut T) -> usize {
+    pointer.addr() & tag_mask::<T>()
+}
+
+/// Returns `pointer` with its tag bits set to `tag`. The bits of `tag` that do not fit in the tag
+/// bits are ignored.
+#[inline]
+pub fn with_tag<T>(pointer: *mut T, tag: usize) -> *mut T {
+    untagged(pointer).map_addr(|addr| addr | (tag & tag_mask::<T>()))
 }
 
 impl Default for Shield {
@@ -70,8 +197,21 @@ impl Default for Shield {
 
 impl Drop for Shield {
     /// Clear and release the ownership of the hazard slot.
+    ///
+    /// Slots of `HAZARDS` are kept active in the thread-local cache if it has room.
     fn drop(&mut self) {
-        todo!()
+        self.clear();
+        #[cfg(feature = "check-hazard")]
+        super::debug::on_shield_drop(&unsafe { self.slot.as_ref() }.hazard);
+        #[cfg(not(feature = "check-loom"))]
+        if self.cache.is_some_and(|cache| {
+            cache
+                .try_with(|slots| slots.borrow_mut().push(self.slot))
+                .is_ok_and(|pushed| pushed)
+        }) {
+            return;
+        }
+        unsafe { self.slot.as_ref() }.release();
     }
 }
 
@@ -87,9 +227,19 @@ impl fmt::Debug for Shield {
 /// Global bag (multiset) of hazards pointers.
 /// `HazardBag.head` and `HazardSlot.next` form a grow-only list of all hazard slots. Slots are
 /// never removed from this list. Instead, it gets deactivated and recycled for other `Shield`s.
+///
+/// The bag also keeps the retired pointers orphaned by the exited threads, which are adopted by
+/// the `RetiredSet`s of the other threads.
 #[derive(Debug)]
 pub struct HazardBag {
     head: AtomicPtr<HazardSlot>,
+    /// The number of active slots, allocated with the first slot and shared with the slots, so
+    /// that a slot is released without the bag, which may have been moved.
+    active_slots: AtomicPtr<AtomicUsize>,
+    orphans: Mutex<Vec<Retired>>,
+    /// Number of running background `Reclaimer`s of this bag.
+    reclaimers: AtomicUsize,
+    counters: Counters,
 }
 
 /// See `HazardBag`
@@ -101,11 +251,77 @@ struct HazardSlot {
     hazard: AtomicPtr<()>,
     // Immutable pointer to the next slot in the bag.
     next: *const HazardSlot,
+    // The number of active slots of the bag.
+    active_slots: *const AtomicUsize,
 }
 
 impl HazardSlot {
-    fn new() -> Self {
-        todo!()
+    fn new(active_slots: &AtomicUsize) -> Self {
+        let _ = active_slots.fetch_add(1, Ordering::Relaxed);
+        Self {
+            active: AtomicBool::new(true),
+            hazard: AtomicPtr::new(ptr::null_mut()),
+            next: ptr::null(),
+            active_slots,
+        }
+    }
+
+    /// Activates this slot if it is inactive.
+    fn try_activate(&self) -> bool {
+        let activated = self
+            .active
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok();
+        if activated {
+            let _ = unsafe { &*self.active_slots }.fetch_add(1, Ordering::Relaxed);
+        }
+        activated
+    }
+
+    /// Deactivates this slot so that it can be acquired by others.
+    fn release(&self) {
+        let _ = unsafe { &*self.active_slots }.fetch_sub(1, Ordering::Relaxed);
+        self.active.store(false, Ordering::Release);
+    }
+}
+
+/// Thread-local cache of the slots of a `HazardBag`, e.g., `SLOTS` of `HAZARDS`.
+///
+/// Cached slots stay active so that no other thread acquires them. They are released to the bag
+/// when the thread exits.
+#[cfg(not(feature = "check-loom"))]
+#[derive(Debug, Default)]
+pub(crate) struct SlotCache {
+    slots: Vec<NonNull<HazardSlot>>,
+}
+
+#[cfg(not(feature = "check-loom"))]
+impl SlotCache {
+    /// The max number of slots kept by a thread.
+    const CAPACITY: usize = 16;
+
+    /// Takes a cached slot.
+    fn pop(&mut self) -> Option<NonNull<HazardSlot>> {
+        self.slots.pop()
+    }
+
+    /// Caches a cleared slot. Returns `false` if the cache is full.
+    fn push(&mut self, slot: NonNull<HazardSlot>) -> bool {
+        if self.slots.len() >= Self::CAPACITY {
+            return false;
+        }
+        self.slots.push(slot);
+        true
+    }
+}
+
+#[cfg(not(feature = "check-loom"))]
+impl Drop for SlotCache {
+    /// Releases all cached slots.
+    fn drop(&mut self) {
+        for slot in self.slots.drain(..) {
+            unsafe { slot.as_ref() }.release();
+        }
     }
 }
 
@@ -115,6 +331,10 @@ impl HazardBag {
     pub const fn new() -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            active_slots: AtomicPtr::new(ptr::null_mut()),
+            orphans: Mutex::new(Vec::new()),
+            reclaimers: AtomicUsize::new(0),
+            counters: Counters::new(),
         }
     }
 
@@ -123,23 +343,143 @@ impl HazardBag {
     pub fn new() -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            active_slots: AtomicPtr::new(ptr::null_mut()),
+            orphans: Mutex::new(Vec::new()),
+            reclaimers: AtomicUsize::new(0),
+            counters: Counters::new(),
         }
     }
 
     /// Acquires a slot in the hazard set, either by recycling an inactive slot or allocating a new
     /// slot.
     fn acquire_slot(&self) -> &HazardSlot {
-        todo!()
+        if let Some(slot) = self.try_acquire_inactive() {
+            return slot;
+        }
+
+        let slot = Box::leak(Box::new(HazardSlot::new(self.active_slots_counter())));
+        self.counters.on_allocate_slot();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            slot.next = head;
+            match self.head.compare_exchange(
+                head,
+                slot as *mut HazardSlot,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return slot,
+                Err(current) => head = current,
+            }
+        }
     }
 
     /// Find an inactive slot and activate it.
     fn try_acquire_inactive(&self) -> Option<&HazardSlot> {
-        todo!()
+        let mut current = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { current.as_ref() } {
+            if slot.try_activate() {
+                return Some(slot);
+            }
+            current = slot.next.cast_mut();
+        }
+        None
     }
 
     /// Returns all the hazards in the set.
     pub fn all_hazards(&self) -> HashSet<*mut ()> {
-        todo!()
+        // Pairs with the fence in `Shield::validate`.
+        fence(Ordering::SeqCst);
+        let mut hazards = HashSet::new();
+        let mut current = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { current.as_ref() } {
+            let hazard = slot.hazard.load(Ordering::Acquire);
+            if !hazard.is_null() {
+                let _ = hazards.insert(hazard);
+            }
+            current = slot.next.cast_mut();
+        }
+        hazards
+    }
+
+    /// Returns `true` if `pointer` is protected by a shield of this bag.
+    pub(crate) fn is_protected(&self, pointer: *mut ()) -> bool {
+        // Pairs with the fence in `Shield::validate`.
+        fence(Ordering::SeqCst);
+        let mut current = self.head.load(Ordering::Acquire);
+        while let Some(slot) = unsafe { current.as_ref() } {
+            if slot.hazard.load(Ordering::Acquire) == pointer {
+                return true;
+            }
+            current = slot.next.cast_mut();
+        }
+        false
+    }
+
+    /// Returns the number of active slots, i.e., the slots occupied by `Shield`s or cached by
+    /// threads.
+    pub fn active_slots(&self) -> usize {
+        unsafe { self.active_slots.load(Ordering::Acquire).as_ref() }
+            .map_or(0, |count| count.load(Ordering::Relaxed))
+    }
+
+    /// Returns the counter of the active slots, allocating it if there is none.
+    fn active_slots_counter(&self) -> &AtomicUsize {
+        let current = self.active_slots.load(Ordering::Acquire);
+        if let Some(count) = unsafe { current.as_ref() } {
+            return count;
+        }
+        let new = Box::into_raw(Box::new(AtomicUsize::new(0)));
+        match self.active_slots.compare_exchange(
+            ptr::null_mut(),
+            new,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => unsafe { &*new },
+            Err(current) => {
+                drop(unsafe { Box::from_raw(new) });
+                unsafe { &*current }
+            }
+        }
+    }
+
+    /// Returns the reclamation statistics of all threads that use this bag.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            active_slots: self.active_slots(),
+            ..self.counters.load()
+        }
+    }
+
+    pub(crate) fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    /// Returns `true` if a background `Reclaimer` is running for this bag.
+    pub(crate) fn has_reclaimer(&self) -> bool {
+        self.reclaimers.load(Ordering::Acquire) != 0
+    }
+
+    /// Registers (`running == true`) or deregisters a background `Reclaimer`.
+    pub(crate) fn set_reclaimer(&self, running: bool) {
+        if running {
+            let _ = self.reclaimers.fetch_add(1, Ordering::AcqRel);
+        } else {
+            let _ = self.reclaimers.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    /// Hands off the retired pointers of an exiting thread to the other threads.
+    pub(crate) fn push_orphans(&self, retired: &mut Vec<Retired>) {
+        self.orphans.lock().unwrap().append(retired);
+    }
+
+    /// Moves the orphaned retired pointers to `retired`, unless another thread is adopting them.
+    pub(crate) fn adopt_orphans(&self, retired: &mut Vec<Retired>) {
+        if let Ok(mut orphans) = self.orphans.try_lock() {
+            retired.append(&mut orphans);
+        }
     }
 }
 
@@ -150,24 +490,55 @@ impl Default for HazardBag {
 }
 
 impl Drop for HazardBag {
-    /// Frees all slots.
+    /// Frees all slots and orphaned retired pointers.
     fn drop(&mut self) {
-        todo!()
+        // No shield can be alive, so none of the orphans are protected.
+        #[cfg(not(feature = "check-loom"))]
+        let orphans = mem::take(self.orphans.get_mut().unwrap());
+        #[cfg(feature = "check-loom")]
+        let orphans = mem::take(&mut *self.orphans.lock().unwrap());
+        for retired in orphans {
+            unsafe { dispose(retired) };
+        }
+
+        #[cfg(not(feature = "check-loom"))]
+        let mut current = *self.head.get_mut();
+        #[cfg(feature = "check-loom")]
+        let mut current = self.head.load(Ordering::Relaxed);
+
+        while !current.is_null() {
+            let slot = unsafe { Box::from_raw(current) };
+            current = slot.next.cast_mut();
+        }
+
+        #[cfg(not(feature = "check-loom"))]
+        let active_slots = *self.active_slots.get_mut();
+        #[cfg(feature = "check-loom")]
+        let active_slots = self.active_slots.load(Ordering::Relaxed);
+        if !active_slots.is_null() {
+            drop(unsafe { Box::from_raw(active_slots) });
+        }
     }
 }
 
+// SAFETY: The orphans are accessed only with the lock held. See `RetiredSet::retire` for sending
+// them to the other threads.
+unsafe impl Send for HazardBag {}
+unsafe impl Sync for HazardBag {}
+
 unsafe impl Send for HazardSlot {}
 unsafe impl Sync for HazardSlot {}
 
 #[cfg(all(test, not(feature = "check-loom")))]
 mod tests {
+    use std::cell::RefCell;
     use std::collections::HashSet;
     use std::ops::Range;
     use std::sync::Arc;
-    use std::sync::atomic::AtomicPtr;
-    use std::{mem, thread};
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::{mem, ptr, thread};
 
-    use super::{HazardBag, Shield};
+    use super::{HazardBag, HazardSlot, Shield, SlotCache, tag, untagged, with_tag};
 
     const THREADS: usize = 8;
     const VALUES: Range<usize> = 1..1024;
@@ -221,6 +592,38 @@ mod tests {
         assert!(intersection.is_empty())
     }
 
+    // tagged pointers should be protected by their untagged addresses regardless of the tags.
+    #[test]
+    fn protect_tagged() {
+        let hazard_bag = HazardBag::new();
+        let data = Box::into_raw(Box::new(0usize));
+        let tagged = with_tag(data, 1);
+        assert_eq!(tag(tagged), 1);
+        assert_eq!(untagged(tagged), data);
+
+        let src = AtomicPtr::new(tagged);
+        let shield = Shield::new(&hazard_bag);
+        assert_eq!(shield.protect_tagged(&src), tagged);
+        assert!(hazard_bag.all_hazards().contains(&(data as *mut ())));
+        assert!(!hazard_bag.all_hazards().contains(&(tagged as *mut ())));
+
+        // the tag of `src` changes
+        src.store(with_tag(data, 2), Ordering::Relaxed);
+        assert!(shield.try_protect_tagged(tagged, &src).is_ok());
+        assert!(shield.try_protect(tagged, &src).is_err());
+
+        // the address of `src` changes
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        assert_eq!(
+            shield.try_protect_tagged(tagged, &src),
+            Err(ptr::null_mut())
+        );
+        assert!(hazard_bag.all_hazards().is_empty());
+
+        drop(shield);
+        drop(unsafe { Box::from_raw(data) });
+    }
+
     // `acquire_slot` should recycle existing slots.
     #[test]
     fn recycle_slots() {
@@ -248,4 +651,60 @@ mod tests {
         // no new slots should've been created
         assert!(new_slots.is_subset(&old_slots));
     }
+
+    // Shields with a cache should reuse the slots cached by the current thread without touching
+    // the bag, and the cached slots should be released when the thread exits.
+    #[test]
+    fn cache_slots() {
+        // Other tests may use `HAZARDS` and `SLOTS`, so the test uses its own bag and cache.
+        static TEST_HAZARDS: HazardBag = HazardBag::new();
+        thread_local! {
+            static TEST_SLOTS: RefCell<SlotCache> = RefCell::new(SlotCache::default());
+        }
+        // SAFETY: `TEST_SLOTS` caches only the slots of `TEST_HAZARDS`.
+        let new_shield = || unsafe { Shield::with_cache(&TEST_HAZARDS, &TEST_SLOTS) };
+        let slot_addrs = |shields: &[Shield]| {
+            shields
+                .iter()
+                .map(|s| s.slot.as_ptr() as usize)
+                .collect::<HashSet<_>>()
+        };
+
+        let old_slots = thread::spawn(move || {
+            let shields = (0..4).map(|_| new_shield()).collect::<Vec<_>>();
+            let old_slots = slot_addrs(&shields);
+            drop(shields);
+
+            let head = TEST_HAZARDS.head.load(Ordering::Relaxed);
+            for _ in 0..1024 {
+                let shields = (0..4).map(|_| new_shield()).collect::<Vec<_>>();
+                assert_eq!(slot_addrs(&shields), old_slots);
+                // cached slots are still active, so no one else can acquire them
+                for slot in &shields {
+                    assert!(unsafe { slot.slot.as_ref() }.active.load(Ordering::Relaxed));
+                }
+            }
+            // no new slots should've been created
+            assert_eq!(TEST_HAZARDS.head.load(Ordering::Relaxed), head);
+            old_slots
+        })
+        .join()
+        .unwrap();
+
+        // the exited thread released its cached slots
+        for &slot in &old_slots {
+            let slot = unsafe { &*(slot as *const HazardSlot) };
+            assert!(!slot.active.load(Ordering::Relaxed));
+        }
+
+        // so that a new thread reacquires them from the bag
+        let new_slots = thread::spawn(move || {
+            (0..4)
+                .map(|_| TEST_HAZARDS.try_acquire_inactive().unwrap() as *const HazardSlot as usize)
+                .collect::<HashSet<_>>()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(new_slots, old_slots);
+    }
 }
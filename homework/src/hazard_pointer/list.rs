@@ -0,0 +1,230 @@
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::marker::PhantomData;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::{mem, ptr};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+use super::{Shield, retire, tag, untagged, with_tag};
+use crate::ConcurrentSet;
+
+/// The tag of `next` that marks its node as logically removed.
+const MARK: usize = 1;
+
+/// Harris-Michael lock-free sorted list set protected by hazard pointers.
+///
+/// A node is logically removed by marking its `next`, and then physically unlinked by the remover
+/// or a traversing thread. As the marked `next` pointers are tagged, shields protect them with
+/// `Shield::protect_tagged`.
+///
+/// # Example
+///
+/// ```
+/// use cs431_homework::{ConcurrentSet, HpListSet};
+///
+/// let set = HpListSet::default();
+/// assert!(set.insert(1));
+/// assert!(!set.insert(1));
+/// assert!(set.contains(&1));
+/// assert!(set.remove(&1));
+/// assert!(!set.contains(&1));
+/// ```
+#[derive(Debug)]
+pub struct HpListSet<T> {
+    /// Never tagged, as it does not belong to a node.
+    head: AtomicPtr<Node<T>>,
+    _marker: PhantomData<Box<Node<T>>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    /// Tagged with `MARK` if this node is logically removed.
+    next: AtomicPtr<Node<T>>,
+}
+
+// `T` is read by all the traversing threads, and may be dropped by any thread that reclaims it.
+unsafe impl<T: Send + Sync> Send for HpListSet<T> {}
+unsafe impl<T: Send + Sync> Sync for HpListSet<T> {}
+
+/// Position in the list found by `HpListSet::find`, with the shields protecting it.
+struct Cursor<T> {
+    /// Either `head` or `next` of the node protected by `prev_shield`.
+    prev: *const AtomicPtr<Node<T>>,
+    /// Untagged. Protected by `curr_shield`.
+    curr: *mut Node<T>,
+    prev_shield: Shield,
+    curr_shield: Shield,
+    next_shield: Shield,
+}
+
+impl<T> Default for HpListSet<T> {
+    fn default() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Ord + Send> HpListSet<T> {
+    /// Creates a new list set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the first node whose data is not less than `value`, unlinking and retiring the
+    /// logically removed nodes on the way. Returns `true` if the data of the node equals `value`.
+    fn find(&self, value: &T) -> (bool, Cursor<T>) {
+        let mut cursor = Cursor {
+            prev: &self.head,
+            curr: ptr::null_mut(),
+            prev_shield: Shield::default(),
+            curr_shield: Shield::default(),
+            next_shield: Shield::default(),
+        };
+
+        'retry: loop {
+            cursor.prev = &self.head;
+            cursor.curr = cursor.curr_shield.protect(&self.head);
+            loop {
+                // SAFETY: `curr` is protected & validated.
+                let Some(curr_ref) = (unsafe { cursor.curr.as_ref() }) else {
+                    return (false, cursor);
+                };
+                // SAFETY: `prev` is `head` or in the node protected by `prev_shield`.
+                let prev = unsafe { &*cursor.prev };
+
+                let next = cursor.next_shield.protect_tagged(&curr_ref.next);
+                // `curr` is still linked from a node that is not removed. Then `next` is not
+                // retired either, as it can only be unlinked from `curr` which is now linked.
+                if prev.load(Ordering::Acquire) != cursor.curr {
+                    continue 'retry;
+                }
+
+                if tag(next) == MARK {
+                    // `curr` is removed. Unlink it and continue with `next`.
+                    let next = untagged(next);
+                    if prev
+                        .compare_exchange(cursor.curr, next, Ordering::AcqRel, Ordering::Acquire)
+                        .is_err()
+                    {
+                        continue 'retry;
+                    }
+                    // SAFETY: `curr` is unlinked by the current thread.
+                    unsafe { retire(cursor.curr) };
+                    cursor.curr = next;
+                    mem::swap(&mut cursor.curr_shield, &mut cursor.next_shield);
+                    continue;
+                }
+
+                match curr_ref.data.cmp(value) {
+                    Less => {
+                        cursor.prev = &curr_ref.next;
+                        cursor.curr = next;
+                        mem::swap(&mut cursor.prev_shield, &mut cursor.curr_shield);
+                        mem::swap(&mut cursor.curr_shield, &mut cursor.next_shield);
+                    }
+                    Equal => return (true, cursor),
+                    Greater => return (false, cursor),
+                }
+            }
+        }
+    }
+}
+
+impl<T: Ord + Send> ConcurrentSet<T> for HpListSet<T> {
+    fn contains(&self, value: &T) -> bool {
+        self.find(value).0
+    }
+
+    fn insert(&self, value: T) -> bool {
+        let mut node = Box::new(Node {
+            data: value,
+            next: AtomicPtr::new(ptr::null_mut()),
+        });
+        loop {
+            let (found, cursor) = self.find(&node.data);
+            if found {
+                return false;
+            }
+
+            node.next.store(cursor.curr, Ordering::Relaxed);
+            let new = Box::into_raw(node);
+            // SAFETY: `prev` is `head` or in the node protected by `prev_shield`.
+            match unsafe { &*cursor.prev }.compare_exchange(
+                cursor.curr,
+                new,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                // SAFETY: `new` is not shared.
+                Err(_) => node = unsafe { Box::from_raw(new) },
+            }
+        }
+    }
+
+    fn remove(&self, value: &T) -> bool {
+        loop {
+            let (found, cursor) = self.find(value);
+            if !found {
+                return false;
+            }
+
+            // SAFETY: `curr` is protected & validated.
+            let curr_ref = unsafe { &*cursor.curr };
+            let next = curr_ref.next.load(Ordering::Acquire);
+            // Someone else is removing `curr`. Retry so that it is unlinked.
+            if tag(next) == MARK {
+                continue;
+            }
+            if curr_ref
+                .next
+                .compare_exchange(
+                    next,
+                    with_tag(next, MARK),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_err()
+            {
+                continue;
+            }
+
+            // SAFETY: `prev` is `head` or in the node protected by `prev_shield`.
+            if unsafe { &*cursor.prev }
+                .compare_exchange(cursor.curr, next, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                // SAFETY: `curr` is unlinked by the current thread.
+                unsafe { retire(cursor.curr) };
+            } else {
+                // Let `find` unlink it.
+                drop(cursor);
+                let _ = self.find(value);
+            }
+            return true;
+        }
+    }
+}
+
+impl<T> Drop for HpListSet<T> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let mut curr = *self.head.get_mut();
+        #[cfg(feature = "check-loom")]
+        let mut curr = self.head.load(Ordering::Relaxed);
+
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(curr) };
+            #[cfg(not(feature = "check-loom"))]
+            let next = node.next.into_inner();
+            #[cfg(feature = "check-loom")]
+            let next = node.next.load(Ordering::Relaxed);
+            curr = untagged(next);
+        }
+    }
+}
@@ -27,11 +27,27 @@ use std::thread_local;
 #[cfg(feature = "check-loom")]
 use loom::thread_local;
 
+#[cfg(feature = "check-hazard")]
+pub mod debug;
+mod domain;
 mod hazard;
+mod list;
+mod queue;
 mod retire;
+mod stack;
+mod stats;
 
-pub use hazard::{HazardBag, Shield};
+pub use domain::Domain;
+#[cfg(not(feature = "check-loom"))]
+use hazard::SlotCache;
+pub use hazard::{HazardBag, Shield, tag, untagged, with_tag};
+pub use list::HpListSet;
+pub use queue::HpQueue;
+#[cfg(not(feature = "check-loom"))]
+pub use retire::Reclaimer;
 pub use retire::RetiredSet;
+pub use stack::HpStack;
+pub use stats::Stats;
 
 #[cfg(not(feature = "check-loom"))]
 /// Default global bag of all hazard pointers.
@@ -45,6 +61,12 @@ loom::lazy_static! {
     pub static ref HAZARDS: HazardBag = HazardBag::new();
 }
 
+#[cfg(not(feature = "check-loom"))]
+thread_local! {
+    /// Thread-local cache of the hazard slots of `HAZARDS`.
+    static SLOTS: RefCell<SlotCache> = RefCell::new(SlotCache::default());
+}
+
 thread_local! {
     /// Default thread-local retired pointer list.
     static RETIRED: RefCell<RetiredSet<'static>> = RefCell::new(RetiredSet::default());
@@ -56,7 +78,12 @@ thread_local! {
 ///
 /// * `pointer` must be removed from shared memory before calling this function, and must be valid.
 /// * The same `pointer` should only be retired once.
-pub unsafe fn retire<T>(pointer: *mut T) {
+///
+/// # Note
+///
+/// `T: Send` is required because the pointers remaining at the exit of the current thread are freed
+/// by the other threads.
+pub unsafe fn retire<T: Send>(pointer: *mut T) {
     RETIRED.with(|r| unsafe { r.borrow_mut().retire(pointer) });
 }
 
@@ -65,3 +92,15 @@ pub unsafe fn retire<T>(pointer: *mut T) {
 pub fn collect() {
     RETIRED.with(|r| r.borrow_mut().collect());
 }
+
+/// Returns the reclamation statistics of the current thread. See `HazardBag::stats` for those of
+/// all threads.
+pub fn stats() -> Stats {
+    RETIRED.with(|r| r.borrow().stats())
+}
+
+/// Sets the ratio of the collection threshold to the number of active hazard slots for the
+/// current thread. See `RetiredSet::with_factor`.
+pub fn set_threshold_factor(factor: usize) {
+    RETIRED.with(|r| r.borrow_mut().set_factor(factor));
+}
@@ -0,0 +1,180 @@
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+use super::{Shield, retire};
+
+/// Michael-Scott lock-free queue protected by hazard pointers.
+///
+/// Usable with any number of producers and consumers.
+///
+/// # Example
+///
+/// ```
+/// use cs431_homework::HpQueue;
+///
+/// let queue = HpQueue::default();
+/// queue.push(1);
+/// queue.push(2);
+/// assert_eq!(queue.try_pop(), Some(1));
+/// assert_eq!(queue.try_pop(), Some(2));
+/// assert_eq!(queue.try_pop(), None);
+/// ```
+#[derive(Debug)]
+pub struct HpQueue<T> {
+    /// Always points to the sentinel node, whose `data` is either uninitialized or moved out.
+    head: AtomicPtr<Node<T>>,
+    tail: AtomicPtr<Node<T>>,
+    _marker: PhantomData<Box<Node<T>>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: MaybeUninit<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+// Any particular `T` is accessed only by its pusher and popper, so no need for `T: Sync`.
+unsafe impl<T: Send> Send for HpQueue<T> {}
+unsafe impl<T: Send> Sync for HpQueue<T> {}
+
+impl<T> Default for HpQueue<T> {
+    fn default() -> Self {
+        let sentinel = Box::leak(Box::new(Node {
+            data: MaybeUninit::uninit(),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+
+        Self {
+            head: AtomicPtr::new(sentinel),
+            tail: AtomicPtr::new(sentinel),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Send> HpQueue<T> {
+    /// Pushes a value to the back of the queue.
+    pub fn push(&self, t: T) {
+        let new = Box::leak(Box::new(Node {
+            data: MaybeUninit::new(t),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        let shield = Shield::default();
+
+        loop {
+            let tail = shield.protect(&self.tail);
+            // SAFETY:
+            // 1. queue's `tail` is always valid as it will be CASed with valid nodes only.
+            // 2. `tail` is protected & validated.
+            let tail_ref = unsafe { &*tail };
+
+            let next = tail_ref.next.load(Ordering::Acquire);
+            if !next.is_null() {
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+
+            if tail_ref
+                .next
+                .compare_exchange(ptr::null_mut(), new, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                let _ = self
+                    .tail
+                    .compare_exchange(tail, new, Ordering::Release, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    /// Pops a value from the front of the queue.
+    ///
+    /// Returns `Some(v)` if `v` is popped; `None` if the queue is empty.
+    pub fn try_pop(&self) -> Option<T> {
+        let head_shield = Shield::default();
+        let next_shield = Shield::default();
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            if let Err(new) = head_shield.try_protect(head, &self.head) {
+                head = new;
+                continue;
+            }
+            // SAFETY:
+            // 1. queue's `head` is always valid as it will be CASed with valid nodes only.
+            // 2. `head` is protected & validated.
+            let head_ref = unsafe { &*head };
+
+            let next = head_ref.next.load(Ordering::Acquire);
+            if next.is_null() {
+                return None;
+            }
+            next_shield.set(next);
+            let next_ref = match Shield::validate(head, &self.head) {
+                // SAFETY:
+                // 1. If `next` was not null, then it must be a valid node that another thread has
+                //    `push()`ed.
+                // 2. Validation: If `head` is not retired, then `next` is not retired. So
+                //    re-validating `head` also validates `next`.
+                Ok(_) => unsafe { &*next },
+                Err(new) => {
+                    head = new;
+                    continue;
+                }
+            };
+
+            let tail = self.tail.load(Ordering::Relaxed);
+            if tail == head {
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+            }
+
+            match self
+                .head
+                .compare_exchange(head, next, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    // SAFETY: Only the thread that made `next` the sentinel reads its data.
+                    let result = unsafe { next_ref.data.assume_init_read() };
+                    // SAFETY: `head` is unlinked by the current thread, and `Node` has no drop
+                    // glue for `T`.
+                    unsafe { retire(head) };
+                    return Some(result);
+                }
+                Err(new) => head = new,
+            }
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        let shield = Shield::default();
+        let head = shield.protect(&self.head);
+        // SAFETY: `head` is protected & validated.
+        unsafe { (*head).next.load(Ordering::Acquire) }.is_null()
+    }
+}
+
+impl<T> Drop for HpQueue<T> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let sentinel = unsafe { Box::from_raw(*self.head.get_mut()) };
+        #[cfg(feature = "check-loom")]
+        let sentinel = unsafe { Box::from_raw(self.head.load(Ordering::Relaxed)) };
+
+        let mut curr = sentinel.next.into_inner();
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(curr) };
+            drop(unsafe { node.data.assume_init() });
+            curr = node.next.into_inner();
+        }
+    }
+}
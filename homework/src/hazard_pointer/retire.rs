@@ -1,38 +1,141 @@
 use core::marker::PhantomData;
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{Ordering, fence};
+use core::sync::atomic::{AtomicBool, Ordering, fence};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::Arc;
+#[cfg(not(feature = "check-loom"))]
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "check-loom")]
 use loom::sync::atomic::{Ordering, fence};
 
-use super::{HAZARDS, HazardBag};
+use super::{HAZARDS, HazardBag, Stats};
+
+/// A retired pointer. The first element of the pair is the machine representation of the pointer
+/// and the second is the function pointer to `free::<T>` where `T` is the type of the object.
+pub(crate) type Retired = (*mut (), unsafe fn(*mut ()));
+
+/// Makes a `Retired` of `pointer`.
+pub(crate) fn new_retired<T>(pointer: *mut T) -> Retired {
+    /// Frees a pointer. This function is defined here instead of `dispose()` as we know about the
+    /// type of `pointer` only at the time of retiring it.
+    ///
+    /// # Safety
+    ///
+    /// * Subsumes the safety requirements of [`Box::from_raw`]. In particular, one must have unique
+    ///   ownership to `data`.
+    ///
+    /// [`Box::from_raw`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw
+    unsafe fn free<T>(data: *mut ()) {
+        drop(unsafe { Box::from_raw(data.cast::<T>()) })
+    }
+
+    #[cfg(feature = "check-hazard")]
+    super::debug::on_retire(pointer.cast());
+    (pointer.cast(), free::<T>)
+}
+
+/// Frees a retired pointer.
+///
+/// # Safety
+///
+/// The pointer must not be protected, and must not be freed yet.
+pub(crate) unsafe fn dispose((pointer, free): Retired) {
+    #[cfg(feature = "check-hazard")]
+    super::debug::on_free(pointer);
+    unsafe { free(pointer) }
+}
+
+/// Frees the pointers in `retired` that are not protected by `hazards`, and returns the number of
+/// the freed pointers. Counted as a `collect` in the statistics of `hazards`.
+pub(crate) fn reclaim(hazards: &HazardBag, retired: &mut Vec<Retired>) -> usize {
+    let start = Instant::now();
+    let len = retired.len();
+    let guarded = hazards.all_hazards();
+    retired.retain(|&retired| {
+        if guarded.contains(&retired.0) {
+            return true;
+        }
+        unsafe { dispose(retired) };
+        false
+    });
+    let freed = len - retired.len();
+    hazards.counters().on_free(freed);
+    hazards.counters().on_collect(start.elapsed());
+    freed
+}
 
-type Retired = (*mut (), unsafe fn(*mut ()));
+/// Returns the length of a retired pointer list that triggers `collect`, i.e., `factor` times the
+/// number of active slots of `hazards`, but at least `RetiredSet::THRESHOLD`.
+pub(crate) fn threshold(hazards: &HazardBag, factor: usize) -> usize {
+    RetiredSet::THRESHOLD.max(factor * hazards.active_slots())
+}
 
 /// Thread-local list of retired pointers.
 #[derive(Debug)]
 pub struct RetiredSet<'s> {
     hazards: &'s HazardBag,
-    /// The first element of the pair is the machine representation of the pointer and the second
-    /// is the function pointer to `free::<T>` where `T` is the type of the object.
+    /// See `Retired`.
     inner: Vec<Retired>,
+    /// The threshold is `factor` times the number of active hazard slots.
+    factor: usize,
+    /// The length of `inner` that triggers `collect`, updated at each `collect`.
+    threshold: usize,
+    /// Statistics of the current thread, except for the slot counts and `pending`.
+    stats: Stats,
     _marker: PhantomData<*const ()>, // !Send + !Sync
 }
 
 impl<'s> RetiredSet<'s> {
-    /// The max length of retired pointer list. `collect` is triggered when `THRESHOLD` pointers
-    /// are retired.
-    const THRESHOLD: usize = 64;
+    /// The min length of retired pointer list that triggers `collect`.
+    pub(crate) const THRESHOLD: usize = 64;
+
+    /// The default ratio of the threshold to the number of active hazard slots.
+    pub const DEFAULT_FACTOR: usize = 2;
 
     /// Create a new retired pointer list protected by the given `HazardBag`.
     pub fn new(hazards: &'s HazardBag) -> Self {
+        Self::with_factor(hazards, Self::DEFAULT_FACTOR)
+    }
+
+    /// Create a new retired pointer list protected by the given `HazardBag`, which is collected
+    /// when the number of retired pointers reaches `factor` times the number of active hazard
+    /// slots (but at least `THRESHOLD`). Then each `collect` frees a constant fraction of the
+    /// pointers, so that the amortized cost of reclamation is constant.
+    pub fn with_factor(hazards: &'s HazardBag, factor: usize) -> Self {
         Self {
             hazards,
             inner: Vec::new(),
+            factor,
+            threshold: Self::THRESHOLD,
+            stats: Stats::default(),
             _marker: PhantomData,
         }
     }
 
+    /// Sets the ratio of the threshold to the number of active hazard slots.
+    pub fn set_factor(&mut self, factor: usize) {
+        self.factor = factor;
+        self.threshold = threshold(self.hazards, factor);
+    }
+
+    /// Returns the number of retired pointers that triggers the next `collect`.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns the reclamation statistics of this list. The slot counts are those of its bag.
+    pub fn stats(&self) -> Stats {
+        let bag = self.hazards.stats();
+        Stats {
+            active_slots: bag.active_slots,
+            allocated_slots: bag.allocated_slots,
+            pending: self.inner.len(),
+            ..self.stats
+        }
+    }
+
     /// Retires a pointer.
     ///
     /// # Safety
@@ -43,28 +146,33 @@ impl<'s> RetiredSet<'s> {
     ///
     /// # Note
     ///
-    /// `T: Send` is not required because the retired pointers are not sent to other threads.
-    pub unsafe fn retire<T>(&mut self, pointer: *mut T) {
-        /// Frees a pointer. This function is defined here instead of `collect()` as we know about
-        /// the type of `pointer` only at the time of retiring it.
-        ///
-        /// # Safety
-        ///
-        /// * Subsumes the safety requirements of [`Box::from_raw`]. In particular, one must have
-        ///   unique ownership to `data`.
-        ///
-        /// [`Box::from_raw`]: https://doc.rust-lang.org/std/boxed/struct.Box.html#method.from_raw
-        unsafe fn free<T>(data: *mut ()) {
-            drop(unsafe { Box::from_raw(data.cast::<T>()) })
+    /// `T: Send` is required because the pointers remaining at the exit of the current thread are
+    /// freed by the other threads.
+    pub unsafe fn retire<T: Send>(&mut self, pointer: *mut T) {
+        self.inner.push(new_retired(pointer));
+        self.hazards.counters().on_retire();
+        self.stats.retired += 1;
+        if self.inner.len() < self.threshold {
+            return;
+        }
+        if self.hazards.has_reclaimer() {
+            self.hazards.push_orphans(&mut self.inner);
+        } else {
+            self.collect();
         }
-
-        todo!()
     }
 
     /// Free the pointers that are `retire`d by the current thread and not `protect`ed by any other
     /// threads.
+    ///
+    /// The pointers orphaned by the exited threads are also adopted and freed.
     pub fn collect(&mut self) {
-        todo!()
+        let start = Instant::now();
+        self.hazards.adopt_orphans(&mut self.inner);
+        self.stats.freed += reclaim(self.hazards, &mut self.inner);
+        self.threshold = threshold(self.hazards, self.factor);
+        self.stats.collects += 1;
+        self.stats.collect_time += start.elapsed();
     }
 }
 
@@ -78,41 +186,216 @@ impl Default for RetiredSet<'static> {
 #[cfg(not(feature = "check-loom"))]
 impl Drop for RetiredSet<'_> {
     fn drop(&mut self) {
-        // In a production-quality implementation of hazard pointers, the remaining local retired
-        // pointers will be moved to a global list of retired pointers, which are then reclaimed by
-        // the other threads. For pedagogical purposes, here we simply wait for all retired pointers
-        // are no longer protected.
-        while !self.inner.is_empty() {
-            self.collect();
+        // The remaining local retired pointers are moved to the orphan list of the hazard bag,
+        // which are then reclaimed by the other threads, so that the exit of the current thread is
+        // not blocked by the shields of the other threads.
+        self.collect();
+        if !self.inner.is_empty() {
+            self.hazards.push_orphans(&mut self.inner);
+        }
+    }
+}
+
+/// Background thread that reclaims the retired pointers of a hazard bag.
+///
+/// While a reclaimer is running, the `RetiredSet`s of the bag hand off their pointers to the
+/// reclaimer instead of collecting them when the threshold is reached. The reclaimer is stopped
+/// when dropped.
+#[cfg(not(feature = "check-loom"))]
+#[derive(Debug)]
+pub struct Reclaimer {
+    hazards: &'static HazardBag,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[cfg(not(feature = "check-loom"))]
+impl Reclaimer {
+    /// Spawns a reclaimer that collects the retired pointers of `hazards` every `interval`.
+    pub fn spawn(hazards: &'static HazardBag, interval: Duration) -> Self {
+        hazards.set_reclaimer(true);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                // The pointers remaining at stop are orphaned again when `retired` is dropped.
+                let mut retired = RetiredSet::new(hazards);
+                while !stop.load(Ordering::Acquire) {
+                    retired.collect();
+                    thread::park_timeout(interval);
+                }
+            })
+        };
+        Self {
+            hazards,
+            stop,
+            handle: Some(handle),
         }
     }
 }
 
+#[cfg(not(feature = "check-loom"))]
+impl Drop for Reclaimer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        let handle = self.handle.take().unwrap();
+        handle.thread().unpark();
+        // A panic of the reclaimer, e.g., in a destructor, is not propagated, as panicking in
+        // `drop` may abort.
+        let _ = handle.join();
+        self.hazards.set_reclaimer(false);
+    }
+}
+
 #[cfg(all(test, not(feature = "check-loom")))]
 mod tests {
-    use std::cell::RefCell;
     use std::collections::HashSet;
-    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
 
-    use super::{HazardBag, RetiredSet};
+    use super::{HazardBag, Reclaimer, RetiredSet};
+    use crate::hazard_pointer::{Shield, Stats};
+
+    struct SyncTester(Arc<Mutex<HashSet<usize>>>, usize);
+    impl Drop for SyncTester {
+        fn drop(&mut self) {
+            let _ = self.0.lock().unwrap().insert(self.1);
+        }
+    }
 
     // retire `THRESHOLD` pointers to trigger collection
     #[test]
     fn retire_threshold_collect() {
-        struct Tester(Rc<RefCell<HashSet<usize>>>, usize);
-        impl Drop for Tester {
-            fn drop(&mut self) {
-                let _ = self.0.borrow_mut().insert(self.1);
-            }
-        }
         let hazards = HazardBag::new();
         let mut retires = RetiredSet::new(&hazards);
-        let freed = Rc::new(RefCell::new(HashSet::new()));
+        let freed = Arc::new(Mutex::new(HashSet::new()));
         for i in 0..RetiredSet::THRESHOLD {
-            unsafe { retires.retire(Box::leak(Box::new(Tester(freed.clone(), i)))) };
+            unsafe { retires.retire(Box::leak(Box::new(SyncTester(freed.clone(), i)))) };
         }
-        let freed = Rc::try_unwrap(freed).unwrap().into_inner();
+        let freed = Arc::try_unwrap(freed).unwrap().into_inner().unwrap();
 
         assert_eq!(freed, (0..RetiredSet::THRESHOLD).collect())
     }
+
+    // a thread should exit without waiting for its protected retired pointers, which are then
+    // freed by the other threads
+    #[test]
+    fn orphans_adopted() {
+        let hazards = HazardBag::new();
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        let pointer = Box::into_raw(Box::new(SyncTester(freed.clone(), 0)));
+        let shield = Shield::new(&hazards);
+        shield.set(pointer);
+
+        let pointer = pointer as usize;
+        thread::scope(|s| {
+            let _ = s.spawn(|| {
+                let mut retires = RetiredSet::new(&hazards);
+                unsafe { retires.retire(pointer as *mut SyncTester) };
+            });
+        });
+        assert!(freed.lock().unwrap().is_empty());
+
+        drop(shield);
+        let mut retires = RetiredSet::new(&hazards);
+        retires.collect();
+        assert!(freed.lock().unwrap().contains(&0));
+    }
+
+    // the threshold should be proportional to the number of active slots
+    #[test]
+    fn retire_threshold_adaptive() {
+        const FACTOR: usize = 4;
+        const SLOTS: usize = 100;
+
+        let hazards = HazardBag::new();
+        let shields = (0..SLOTS)
+            .map(|_| Shield::new(&hazards))
+            .collect::<Vec<_>>();
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        let mut retires = RetiredSet::with_factor(&hazards, FACTOR);
+        // the first collection updates the threshold
+        retires.collect();
+        assert_eq!(retires.threshold(), FACTOR * SLOTS);
+
+        for i in 0..FACTOR * SLOTS - 1 {
+            unsafe { retires.retire(Box::into_raw(Box::new(SyncTester(freed.clone(), i)))) };
+        }
+        assert!(freed.lock().unwrap().is_empty());
+        let last = FACTOR * SLOTS - 1;
+        unsafe { retires.retire(Box::into_raw(Box::new(SyncTester(freed.clone(), last)))) };
+        assert_eq!(
+            *freed.lock().unwrap(),
+            (0..FACTOR * SLOTS).collect::<HashSet<_>>()
+        );
+
+        // fewer slots, lower threshold
+        drop(shields);
+        retires.collect();
+        assert_eq!(retires.threshold(), RetiredSet::THRESHOLD);
+    }
+
+    // the statistics should count the slots, the retired and freed pointers, and collections
+    #[test]
+    fn stats() {
+        const COUNT: usize = 16;
+
+        let hazards = HazardBag::new();
+        let shields = [Shield::new(&hazards), Shield::new(&hazards)];
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        let mut retires = RetiredSet::new(&hazards);
+        for i in 0..COUNT {
+            let pointer = Box::into_raw(Box::new(SyncTester(freed.clone(), i)));
+            if i == 0 {
+                shields[0].set(pointer);
+            }
+            unsafe { retires.retire(pointer) };
+        }
+        retires.collect();
+
+        let stats = retires.stats();
+        assert_eq!(stats.active_slots, 2);
+        assert_eq!(stats.allocated_slots, 2);
+        assert_eq!(stats.retired, COUNT);
+        assert_eq!(stats.freed, COUNT - 1);
+        assert_eq!(stats.pending, 1);
+        assert_eq!(stats.collects, 1);
+        assert_eq!(
+            Stats {
+                collect_time: stats.collect_time,
+                ..hazards.stats()
+            },
+            stats
+        );
+
+        drop(shields);
+        retires.collect();
+        let stats = hazards.stats();
+        assert_eq!(stats.active_slots, 0);
+        assert_eq!(stats.allocated_slots, 2);
+        assert_eq!(stats.freed, COUNT);
+        assert_eq!(stats.pending, 0);
+        assert_eq!(stats.collects, 2);
+    }
+
+    // retired pointers should be handed off to and freed by the background reclaimer
+    #[test]
+    fn background_reclaimer() {
+        static HAZARDS: HazardBag = HazardBag::new();
+
+        let reclaimer = Reclaimer::spawn(&HAZARDS, Duration::from_millis(1));
+        let freed = Arc::new(Mutex::new(HashSet::new()));
+        let mut retires = RetiredSet::new(&HAZARDS);
+        for i in 0..RetiredSet::THRESHOLD {
+            unsafe { retires.retire(Box::into_raw(Box::new(SyncTester(freed.clone(), i)))) };
+        }
+
+        let start = Instant::now();
+        while freed.lock().unwrap().len() < RetiredSet::THRESHOLD {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(reclaimer);
+    }
 }
@@ -0,0 +1,123 @@
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+use super::{Shield, retire};
+
+/// Treiber's lock-free stack protected by hazard pointers.
+///
+/// Usable with any number of producers and consumers.
+///
+/// # Example
+///
+/// ```
+/// use cs431_homework::HpStack;
+///
+/// let stack = HpStack::default();
+/// stack.push(1);
+/// stack.push(2);
+/// assert_eq!(stack.try_pop(), Some(2));
+/// assert_eq!(stack.try_pop(), Some(1));
+/// assert_eq!(stack.try_pop(), None);
+/// ```
+#[derive(Debug)]
+pub struct HpStack<T> {
+    head: AtomicPtr<Node<T>>,
+    _marker: PhantomData<Box<Node<T>>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    /// Moved out by the popper before the node is retired.
+    data: MaybeUninit<T>,
+    /// Immutable once the node is pushed.
+    next: *mut Node<T>,
+}
+
+// Any particular `T` is accessed only by its pusher and popper, so no need for `T: Sync`.
+unsafe impl<T: Send> Send for HpStack<T> {}
+unsafe impl<T: Send> Sync for HpStack<T> {}
+
+// A popped node is retired, and may be freed by another thread.
+unsafe impl<T: Send> Send for Node<T> {}
+
+impl<T> Default for HpStack<T> {
+    fn default() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Send> HpStack<T> {
+    /// Pushes a value to the stack.
+    pub fn push(&self, t: T) {
+        let new = Box::leak(Box::new(Node {
+            data: MaybeUninit::new(t),
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            new.next = head;
+            match self
+                .head
+                .compare_exchange(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Pops a value from the stack.
+    ///
+    /// Returns `Some(v)` if `v` is popped; `None` if the stack is empty.
+    pub fn try_pop(&self) -> Option<T> {
+        let shield = Shield::default();
+        loop {
+            let head = shield.protect(&self.head);
+            // SAFETY: `head` is protected & validated, so it is not freed yet.
+            let head_ref = unsafe { head.as_ref() }?;
+
+            if self
+                .head
+                .compare_exchange(head, head_ref.next, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                // SAFETY: Only the thread that unlinked `head` reads its data.
+                let data = unsafe { head_ref.data.assume_init_read() };
+                // SAFETY: `head` is unlinked by the current thread, and `Node` has no drop glue
+                // for `T`.
+                unsafe { retire(head) };
+                return Some(data);
+            }
+        }
+    }
+
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T> Drop for HpStack<T> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "check-loom"))]
+        let mut curr = *self.head.get_mut();
+        #[cfg(feature = "check-loom")]
+        let mut curr = self.head.load(Ordering::Relaxed);
+
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(curr) };
+            drop(unsafe { node.data.assume_init() });
+            curr = node.next;
+        }
+    }
+}
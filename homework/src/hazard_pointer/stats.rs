@@ -0,0 +1,97 @@
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Reclamation statistics of a `HazardBag` (all threads) or a `RetiredSet` (a thread), returned
+/// by their `stats()`.
+///
+/// The slot counts are always those of the hazard bag.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of slots occupied by `Shield`s or cached by threads.
+    pub active_slots: usize,
+    /// The number of slots ever allocated, i.e., the length of the bag.
+    pub allocated_slots: usize,
+    /// The number of pointers retired.
+    pub retired: usize,
+    /// The number of retired pointers freed.
+    pub freed: usize,
+    /// The number of retired pointers not freed yet. For a `RetiredSet`, this does not include the
+    /// pointers handed off to the orphan list or a `Reclaimer`.
+    pub pending: usize,
+    /// The number of `collect` invocations.
+    pub collects: usize,
+    /// The total time spent in `collect`.
+    pub collect_time: Duration,
+}
+
+/// Counters of a `HazardBag`, shared by the threads.
+#[derive(Debug)]
+pub(crate) struct Counters {
+    allocated_slots: AtomicUsize,
+    retired: AtomicUsize,
+    freed: AtomicUsize,
+    collects: AtomicUsize,
+    collect_nanos: AtomicU64,
+}
+
+impl Counters {
+    #[cfg(not(feature = "check-loom"))]
+    pub(crate) const fn new() -> Self {
+        Self {
+            allocated_slots: AtomicUsize::new(0),
+            retired: AtomicUsize::new(0),
+            freed: AtomicUsize::new(0),
+            collects: AtomicUsize::new(0),
+            collect_nanos: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg(feature = "check-loom")]
+    pub(crate) fn new() -> Self {
+        Self {
+            allocated_slots: AtomicUsize::new(0),
+            retired: AtomicUsize::new(0),
+            freed: AtomicUsize::new(0),
+            collects: AtomicUsize::new(0),
+            collect_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn on_allocate_slot(&self) {
+        let _ = self.allocated_slots.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_retire(&self) {
+        let _ = self.retired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_free(&self, count: usize) {
+        let _ = self.freed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_collect(&self, time: Duration) {
+        let _ = self.collects.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self.collect_nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics except for `active_slots`.
+    pub(crate) fn load(&self) -> Stats {
+        let retired = self.retired.load(Ordering::Relaxed);
+        let freed = self.freed.load(Ordering::Relaxed);
+        Stats {
+            active_slots: 0,
+            allocated_slots: self.allocated_slots.load(Ordering::Relaxed),
+            retired,
+            freed,
+            // The counters are loaded separately, so `freed` may be ahead of `retired`.
+            pending: retired.saturating_sub(freed),
+            collects: self.collects.load(Ordering::Relaxed),
+            collect_time: Duration::from_nanos(self.collect_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
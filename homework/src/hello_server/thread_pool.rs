@@ -19,9 +19,12 @@ impl Drop for Worker {
     /// When dropped, the thread's `JoinHandle` must be `join`ed.  If the worker panics, then this
     /// function should panic too.
     ///
-    /// NOTE: The thread is detached if not `join`ed explicitly.
+    /// NOTE: The thread is detached if not `join`ed explicitly, or if the pool has already taken
+    /// its `JoinHandle` to detach it.
     fn drop(&mut self) {
-        self.thread.take().unwrap().join().unwrap()
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap()
+        }
     }
 }
 
@@ -127,7 +130,23 @@ impl ThreadPool {
 impl Drop for ThreadPool {
     /// When dropped, all worker threads' `JoinHandle` must be `join`ed. If the thread panicked,
     /// then this function should panic too.
+    ///
+    /// If the pool is dropped by one of its jobs, e.g., with the last `Arc` to the pool, a worker
+    /// cannot join itself, so the workers are detached instead. They exit after running the
+    /// remaining jobs.
     fn drop(&mut self) {
         drop(self.job_sender.take());
+        let current = thread::current().id();
+        let in_worker = self._workers.iter().any(|worker| {
+            worker
+                .thread
+                .as_ref()
+                .is_some_and(|thread| thread.thread().id() == current)
+        });
+        if in_worker {
+            for worker in &mut self._workers {
+                drop(worker.thread.take());
+            }
+        }
     }
 }
@@ -8,9 +8,13 @@
 
 mod adt;
 mod arc;
+mod atomic_arc;
+pub mod biased_arc;
 pub mod boc;
 mod elim_stack;
 mod hash_table;
+#[cfg(not(feature = "check-loom"))]
+pub mod hazard_era;
 pub mod hazard_pointer;
 pub mod hello_server;
 mod linked_list;
@@ -19,9 +23,12 @@ mod list_set;
 pub mod test;
 
 pub use adt::{ConcurrentMap, ConcurrentSet};
-pub use arc::Arc;
+pub use arc::{AllocError, Arc, Weak};
+pub use atomic_arc::AtomicArc;
+pub use biased_arc::BiasedArc;
 pub use boc::CownPtr;
 pub use elim_stack::ElimStack;
 pub use hash_table::{GrowableArray, SplitOrderedList};
+pub use hazard_pointer::{HpListSet, HpQueue, HpStack};
 pub use linked_list::LinkedList;
 pub use list_set::{FineGrainedListSet, OptimisticFineGrainedListSet};
@@ -15,11 +15,15 @@ impl Drop for Canary {
 
 #[cfg(not(feature = "check-loom"))]
 mod basic {
-    use cs431_homework::Arc;
+    use std::fmt::Display;
+    use std::panic::{self, AssertUnwindSafe};
+
+    use cs431_homework::hazard_pointer::collect;
     use cs431_homework::test::loom::sync::atomic::AtomicUsize;
     use cs431_homework::test::loom::sync::atomic::Ordering::Relaxed;
     use cs431_homework::test::loom::sync::mpsc::channel;
     use cs431_homework::test::loom::thread;
+    use cs431_homework::{Arc, AtomicArc, BiasedArc, Weak, unsize_arc};
 
     use super::Canary;
 
@@ -124,6 +128,383 @@ mod basic {
         assert!(canary.load(Relaxed) == 1);
     }
 
+    #[test]
+    fn test_weak_count() {
+        let a = Arc::new(0);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 0);
+        let w = Arc::downgrade(&a);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 1);
+        let x = w.clone();
+        assert!(Arc::weak_count(&a) == 2);
+        drop(w);
+        drop(x);
+        assert!(Arc::count(&a) == 1);
+        assert!(Arc::weak_count(&a) == 0);
+        let c = a.clone();
+        assert!(Arc::count(&a) == 2);
+        assert!(Arc::weak_count(&a) == 0);
+        let d = Arc::downgrade(&c);
+        assert!(Arc::weak_count(&c) == 1);
+        assert!(Arc::count(&c) == 2);
+        drop(a);
+        drop(c);
+        drop(d);
+    }
+
+    #[test]
+    fn test_live_upgrade() {
+        let x = Arc::new(5);
+        let y = Arc::downgrade(&x);
+        assert!(y.upgrade().is_some());
+        assert!(Arc::count(&x) == 1);
+        let z = y.upgrade().unwrap();
+        assert!(Arc::count(&x) == 2);
+        assert!(Arc::ptr_eq(&x, &z));
+    }
+
+    #[test]
+    fn test_dead_upgrade() {
+        let x = Arc::new(5);
+        let y = Arc::downgrade(&x);
+        drop(x);
+        assert!(y.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_new_weak() {
+        let foo: Weak<usize> = Weak::new();
+        assert!(foo.upgrade().is_none());
+        let _ = foo.clone();
+    }
+
+    #[test]
+    fn drop_arc_weak() {
+        let canary = AtomicUsize::new(0);
+        let arc = Arc::new(Canary(&canary));
+        let arc_weak = Arc::downgrade(&arc);
+        assert!(canary.load(Relaxed) == 0);
+        drop(arc);
+        assert!(canary.load(Relaxed) == 1);
+        drop(arc_weak);
+    }
+
+    #[test]
+    fn test_cowarc_clone_weak() {
+        let mut cow0 = Arc::new(75);
+        let cow1_weak = Arc::downgrade(&cow0);
+
+        assert!(75 == *cow0);
+        assert!(75 == *cow1_weak.upgrade().unwrap());
+
+        *Arc::make_mut(&mut cow0) += 1;
+
+        assert!(76 == *cow0);
+        assert!(cow1_weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_get_mut_weak() {
+        let mut x = Arc::new(3);
+        let w = Arc::downgrade(&x);
+        assert!(Arc::get_mut(&mut x).is_none());
+        drop(w);
+        *Arc::get_mut(&mut x).unwrap() = 4;
+        assert_eq!(*x, 4);
+    }
+
+    #[test]
+    fn test_try_unwrap_weak() {
+        let x = Arc::new(3);
+        let w = Arc::downgrade(&x);
+        assert_eq!(Arc::try_unwrap(x).ok(), Some(3));
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_parent_back_pointer() {
+        struct Node {
+            parent: Weak<Node>,
+            _canary: Canary,
+        }
+
+        let canary = AtomicUsize::new(0);
+        let parent = Arc::new(Node {
+            parent: Weak::new(),
+            _canary: Canary(&canary),
+        });
+        let child = Arc::new(Node {
+            parent: Arc::downgrade(&parent),
+            _canary: Canary(&canary),
+        });
+        assert!(Arc::ptr_eq(&child.parent.upgrade().unwrap(), &parent));
+        assert!(parent.parent.upgrade().is_none());
+
+        drop(parent);
+        assert!(canary.load(Relaxed) == 1);
+        assert!(child.parent.upgrade().is_none());
+        drop(child);
+        assert!(canary.load(Relaxed) == 2);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let r: Arc<str> = Arc::from("foo");
+        assert_eq!(&r[..], "foo");
+
+        let s: Arc<str> = Arc::from(String::from("bar"));
+        assert_eq!(&s[..], "bar");
+        let t = s.clone();
+        assert!(Arc::ptr_eq(&s, &t));
+        assert!(Arc::count(&s) == 2);
+    }
+
+    #[test]
+    fn test_from_vec() {
+        let v = vec![1, 2, 3];
+        let r: Arc<[u32]> = Arc::from(v);
+        assert_eq!(&r[..], [1, 2, 3]);
+
+        let r: Arc<[u64]> = Arc::from(&[4, 5, 6][..]);
+        assert_eq!(&r[..], [4, 5, 6]);
+
+        let r: Arc<[()]> = Arc::from(vec![(); 4]);
+        assert_eq!(r.len(), 4);
+
+        let r: Arc<[u8]> = Arc::from(Vec::new());
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn drop_slice_once() {
+        let canary = AtomicUsize::new(0);
+        let v = (0..4).map(|_| Canary(&canary)).collect::<Vec<_>>();
+        let x: Arc<[Canary]> = Arc::from(v);
+        let y = x.clone();
+        let w = Arc::downgrade(&x);
+        drop(x);
+        assert!(canary.load(Relaxed) == 0);
+        drop(y);
+        assert!(canary.load(Relaxed) == 4);
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn clone_slice_panic() {
+        struct Panicky<'a>(&'a AtomicUsize, bool);
+        impl Clone for Panicky<'_> {
+            fn clone(&self) -> Self {
+                assert!(!self.1, "clone");
+                Panicky(self.0, false)
+            }
+        }
+        impl Drop for Panicky<'_> {
+            fn drop(&mut self) {
+                let _ = self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        let dropped = AtomicUsize::new(0);
+        let v = [
+            Panicky(&dropped, false),
+            Panicky(&dropped, false),
+            Panicky(&dropped, true),
+        ];
+        let result = panic::catch_unwind(AssertUnwindSafe(|| Arc::<[Panicky<'_>]>::from(&v[..])));
+        assert!(result.is_err());
+        // the two clones are dropped
+        assert_eq!(dropped.load(Relaxed), 2);
+    }
+
+    #[test]
+    fn test_unsize() {
+        let canary = AtomicUsize::new(0);
+        let x: Arc<dyn Display> = unsize_arc!(Arc::new(42u64), dyn Display);
+        assert_eq!(x.to_string(), "42");
+        let y = x.clone();
+        assert!(Arc::ptr_eq(&x, &y));
+
+        // larger alignment than the counts
+        #[repr(align(64))]
+        struct Aligned(u8);
+        let a: Arc<[Aligned]> = unsize_arc!(Arc::new([Aligned(1), Aligned(2)]), [Aligned]);
+        assert_eq!(a[1].0, 2);
+        assert!((&a[0] as *const Aligned).addr() % 64 == 0);
+
+        let c: Arc<dyn Send + Sync> = unsize_arc!(Arc::new(Canary(&canary)), dyn Send + Sync);
+        let w = Arc::downgrade(&c);
+        drop(c);
+        assert!(canary.load(Relaxed) == 1);
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_new_cyclic() {
+        struct Node {
+            me: Weak<Node>,
+            _canary: Canary,
+        }
+
+        let canary = AtomicUsize::new(0);
+        let node = Arc::new_cyclic(|me| {
+            assert!(me.upgrade().is_none());
+            Node {
+                me: me.clone(),
+                _canary: Canary(&canary),
+            }
+        });
+        assert!(Arc::ptr_eq(&node, &node.me.upgrade().unwrap()));
+        assert!(Arc::count(&node) == 1);
+        assert!(Arc::weak_count(&node) == 1);
+
+        let me = node.me.clone();
+        drop(node);
+        assert!(canary.load(Relaxed) == 1);
+        assert!(me.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_new_uninit() {
+        let mut x = Arc::<[u64; 128]>::new_uninit();
+        let _ = Arc::get_mut(&mut x).unwrap().write([7; 128]);
+        let x = unsafe { x.assume_init() };
+        assert!(x.iter().all(|&v| v == 7));
+        assert!(Arc::count(&x) == 1);
+    }
+
+    #[test]
+    fn test_try_new() {
+        let x = Arc::try_new(5).unwrap();
+        assert_eq!(*x, 5);
+    }
+
+    #[test]
+    fn test_into_from_raw() {
+        let canary = AtomicUsize::new(0);
+        let x = Arc::new(Canary(&canary));
+        let y = x.clone();
+        let ptr = Arc::into_raw(x);
+        assert!(Arc::count(&y) == 2);
+        assert!(std::ptr::eq(ptr, Arc::as_ptr(&y)));
+
+        unsafe {
+            Arc::increment_strong_count(ptr);
+            assert!(Arc::count(&y) == 3);
+            Arc::decrement_strong_count(ptr);
+            assert!(Arc::count(&y) == 2);
+            drop(Arc::from_raw(ptr));
+        }
+        assert!(Arc::count(&y) == 1);
+        drop(y);
+        assert!(canary.load(Relaxed) == 1);
+    }
+
+    #[test]
+    fn unsized_into_from_raw() {
+        let x: Arc<str> = Arc::from("hello");
+        let ptr = Arc::into_raw(x);
+        unsafe {
+            Arc::increment_strong_count(ptr);
+            let x = Arc::from_raw(ptr);
+            assert_eq!(&*x, "hello");
+            assert!(Arc::count(&x) == 2);
+            Arc::decrement_strong_count(ptr);
+            assert!(Arc::count(&x) == 1);
+        }
+
+        let canary = AtomicUsize::new(0);
+        let x = unsize_arc!(Arc::new((42u64, Canary(&canary))), dyn Send);
+        let w = Arc::downgrade(&x);
+        let x = unsafe { Arc::from_raw(Arc::into_raw(x)) };
+        drop(x);
+        assert!(canary.load(Relaxed) == 1);
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn new_cyclic_panic() {
+        let mut me = Weak::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            Arc::<u64>::new_cyclic(|weak| {
+                me = weak.clone();
+                panic!("new_cyclic");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(me.upgrade().is_none());
+        // frees the allocation, whose data is never written
+        drop(me);
+    }
+
+    #[test]
+    fn test_unwrap_or_clone() {
+        let x = Arc::new(vec![1, 2]);
+        let y = x.clone();
+        assert_eq!(Arc::unwrap_or_clone(x), [1, 2]);
+        let ptr = y.as_ptr();
+        let z = Arc::unwrap_or_clone(y);
+        assert_eq!(z.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn atomic_arc_swap() {
+        let canary = AtomicUsize::new(0);
+        let cell = AtomicArc::new(Arc::new(Canary(&canary)));
+        let first = cell.load();
+        let old = cell.swap(Arc::new(Canary(&canary)));
+        assert!(Arc::ptr_eq(&first, &old));
+        assert!(!Arc::ptr_eq(&first, &cell.load()));
+        // no extra count is held for the concurrent loads
+        assert!(Arc::count(&old) == 2);
+
+        drop(first);
+        drop(old);
+        collect();
+        assert!(canary.load(Relaxed) == 1);
+        drop(cell);
+        assert!(canary.load(Relaxed) == 2);
+    }
+
+    #[test]
+    fn atomic_arc_compare_exchange() {
+        let one = Arc::new(1);
+        let two = Arc::new(2);
+        let cell = AtomicArc::new(one.clone());
+
+        let (current, new) = cell.compare_exchange(&two, Arc::new(3)).unwrap_err();
+        assert!(Arc::ptr_eq(&current, &one));
+        assert_eq!(*new, 3);
+
+        let old = cell.compare_exchange(&one, two.clone()).unwrap();
+        assert!(Arc::ptr_eq(&old, &one));
+        assert!(Arc::ptr_eq(&cell.into_inner(), &two));
+    }
+
+    #[test]
+    fn atomic_arc_stress() {
+        const THREADS: usize = 8;
+        const ITER: usize = 1024;
+
+        let cell = AtomicArc::new(Arc::new(0));
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                let _ = s.spawn(|| {
+                    for _ in 0..ITER {
+                        let mut current = cell.load();
+                        while let Err((actual, _)) =
+                            cell.compare_exchange(&current, Arc::new(*current + 1))
+                        {
+                            current = actual;
+                        }
+                        assert!(*cell.load() > 0);
+                    }
+                });
+            }
+        });
+        assert_eq!(*cell.load(), THREADS * ITER);
+    }
+
     #[test]
     fn test_stress() {
         let count = Arc::new(AtomicUsize::new(0));
@@ -142,13 +523,94 @@ mod basic {
         }
         assert_eq!(count.load(Relaxed), 8 * 128);
     }
+
+    #[test]
+    fn biased_arc_owner() {
+        let canary = AtomicUsize::new(0);
+        let x = BiasedArc::new(Canary(&canary));
+        let y = x.clone();
+        assert!(BiasedArc::ptr_eq(&x, &y));
+        drop(x);
+        assert!(canary.load(Relaxed) == 0);
+        drop(y);
+        assert!(canary.load(Relaxed) == 1);
+    }
+
+    #[test]
+    fn biased_arc_owner_drops_last() {
+        let canary = AtomicUsize::new(0);
+        let x = BiasedArc::new(Canary(&canary));
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let y = x.clone();
+                let _ = s.spawn(move || {
+                    for _ in 0..128 {
+                        drop(y.clone());
+                    }
+                    drop(y);
+                });
+            }
+        });
+        assert!(canary.load(Relaxed) == 0);
+        drop(x);
+        assert!(canary.load(Relaxed) == 1);
+    }
+
+    #[test]
+    fn biased_arc_others_drop_last() {
+        let canary = AtomicUsize::new(0);
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            let x = BiasedArc::new(Canary(&canary));
+            for _ in 0..4 {
+                tx.send(x.clone()).unwrap();
+            }
+            drop(tx);
+            let _ = s.spawn(move || {
+                while let Ok(y) = rx.recv() {
+                    drop(y.clone());
+                    drop(y);
+                }
+            });
+            drop(x);
+            // Merges the counts queued by the other thread.
+            while canary.load(Relaxed) == 0 {
+                drop(BiasedArc::new(()));
+                thread::yield_now();
+            }
+        });
+        assert!(canary.load(Relaxed) == 1);
+    }
+
+    #[test]
+    fn biased_arc_owner_exits_first() {
+        let canary = AtomicUsize::new(0);
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            s.spawn(|| {
+                let x = BiasedArc::new(Canary(&canary));
+                for _ in 0..4 {
+                    tx.send(x.clone()).unwrap();
+                }
+            })
+            .join()
+            .unwrap();
+            assert!(canary.load(Relaxed) == 0);
+
+            for _ in 0..4 {
+                let y = rx.recv().unwrap();
+                let _ = s.spawn(move || drop(y));
+            }
+        });
+        assert!(canary.load(Relaxed) == 1);
+    }
 }
 
 mod correctness {
-    use cs431_homework::Arc;
     use cs431_homework::test::loom::sync::atomic::AtomicUsize;
     use cs431_homework::test::loom::sync::atomic::Ordering::Relaxed;
     use cs431_homework::test::loom::{model, thread};
+    use cs431_homework::{Arc, AtomicArc};
 
     use super::Canary;
 
@@ -227,6 +689,44 @@ mod correctness {
         })
     }
 
+    #[test]
+    /// Resistance against arbitrary interleaving of `upgrade` and the last `drop`.
+    fn upgrade_drop_atomic() {
+        model(|| {
+            let canary = AtomicUsize::new(0);
+            let arc = Arc::new(Canary(&canary));
+            let weak = Arc::downgrade(&arc);
+            let handle = thread::spawn(move || {
+                drop(weak.upgrade());
+            });
+            drop(arc);
+            handle.join().unwrap();
+            assert_eq!(canary.load(Relaxed), 1);
+        })
+    }
+
+    #[test]
+    /// value:=123 → store → load → value==123
+    fn atomic_arc_sync() {
+        model(|| {
+            let initial = Arc::new(AtomicUsize::new(0));
+            let cell = Arc::new(AtomicArc::new(initial.clone()));
+            let handle = {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    let value = Arc::new(AtomicUsize::new(0));
+                    value.store(123, Relaxed);
+                    cell.store(value);
+                })
+            };
+            let value = cell.load();
+            if !Arc::ptr_eq(&value, &initial) {
+                assert_eq!(value.load(Relaxed), 123);
+            }
+            handle.join().unwrap();
+        })
+    }
+
     #[test]
     /// Resistance against arbitrary interleaving of instructions in `clone` and `drop`.
     fn clone_drop_atomic() {
@@ -215,12 +215,22 @@ mod boc_merge_sort {
 }
 
 mod basic_test {
-    use std::sync::Arc;
+    use std::future::Future;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::pin::pin;
     use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
 
     use crossbeam_channel::bounded;
-    use cs431_homework::boc::{CownPtr, run_when};
+    use cs431_homework::boc::{
+        BocRuntime, CownPtr, DeterministicScheduler, Priority, RayonScheduler, run_when,
+        run_when_batch,
+    };
+    use cs431_homework::hello_server::ThreadPool;
     use cs431_homework::{tuple_list, when};
+    use rayon::ThreadPoolBuilder;
 
     use crate::{boc_banking, boc_fibonacci, boc_merge_sort};
 
@@ -313,6 +323,450 @@ mod basic_test {
         }
     }
 
+    #[test]
+    fn behavior_handle_wait() {
+        let c1 = CownPtr::new(1);
+        let c2 = CownPtr::new(2);
+        let sum = when!(c1, c2; g1, g2; {
+            *g1 += 1;
+            *g1 + *g2
+        });
+        assert_eq!(sum.wait(), 4);
+        assert_eq!(when!(c1; g1; *g1).wait(), 2);
+
+        let sums = (0..4)
+            .map(|i| run_when(vec![c1.clone(), c2.clone()], move |x| *x[0] + *x[1] + i))
+            .collect::<Vec<_>>();
+        for (i, sum) in sums.into_iter().enumerate() {
+            assert_eq!(sum.wait(), 4 + i);
+        }
+    }
+
+    #[test]
+    fn behavior_handle_future() {
+        struct Unparker(Thread);
+
+        impl Wake for Unparker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        /// Polls `future` on the current thread until it is ready.
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let mut future = pin!(future);
+            let waker = Waker::from(Arc::new(Unparker(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            loop {
+                match future.as_mut().poll(&mut cx) {
+                    Poll::Ready(output) => return output,
+                    Poll::Pending => thread::park(),
+                }
+            }
+        }
+
+        let c1 = CownPtr::new(String::from("behavior"));
+        let len = block_on(async {
+            when!(c1; g1; g1.push_str(" of cowns")).await;
+            when!(c1; g1; g1.len()).await
+        });
+        assert_eq!(len, "behavior of cowns".len());
+    }
+
+    #[test]
+    fn read_parallel() {
+        // The behaviors created on a pool's thread run on the pool, which has two threads even on a
+        // single core.
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let c1 = CownPtr::new(1);
+        let barrier = Arc::new(Barrier::new(2));
+        let c1_ = c1.clone();
+        let readers = pool.install(move || {
+            let c1 = c1_;
+            let readers = (0..2)
+                .map(|_| {
+                    let barrier = barrier.clone();
+                    // Both readers should run at the same time to pass the barrier.
+                    when!(read c1; g1; {
+                        let _ = barrier.wait();
+                        *g1
+                    })
+                })
+                .collect::<Vec<_>>();
+            when!(c1; g1; *g1 += 1);
+            readers
+        });
+        for reader in readers {
+            assert_eq!(reader.wait(), 1);
+        }
+        assert_eq!(when!(read c1; g1; *g1).wait(), 2);
+    }
+
+    #[test]
+    fn panic_poison_before_wait() {
+        for _ in 0..100 {
+            let c = CownPtr::new(0);
+            let panicked = when!(c; g; {
+                *g += 1;
+                panic!("inconsistent");
+            });
+            // The cown is poisoned by the time the waiter sees the panic.
+            assert!(panic::catch_unwind(AssertUnwindSafe(|| panicked.wait())).is_err());
+            assert!(c.is_poisoned());
+        }
+    }
+
+    #[test]
+    fn read_write_order() {
+        let c1 = CownPtr::new(0);
+        let c2 = CownPtr::new(0);
+        let mut writes = 0;
+        let mut handles = Vec::new();
+        for i in 0..1024 {
+            if i % 4 == 0 {
+                writes += 1;
+                when!(c1; g1; *g1 += 1);
+            } else if i % 4 == 1 {
+                when!(read c1, c2; g1, g2; *g2 += *g1);
+            } else {
+                handles.push(when!(read c1; g1; *g1 == writes));
+            }
+        }
+        assert!(handles.into_iter().all(|handle| handle.wait()));
+        let sum = (1..=256).sum::<usize>();
+        assert_eq!(when!(read c2; g2; *g2).wait(), sum);
+    }
+
+    #[test]
+    fn when_mixed() {
+        let c1 = CownPtr::new(1);
+        let c2 = CownPtr::new(String::from("total"));
+        let accounts = (0..4).map(CownPtr::new).collect::<Vec<_>>();
+        let limits = accounts
+            .iter()
+            .map(|_| CownPtr::new(10))
+            .collect::<Box<[_]>>();
+        when!(c1, read c2, accounts, limits; g1, g2, accs, lims; {
+            for (acc, lim) in accs.into_iter().zip(lims) {
+                *acc += *g1;
+                *lim -= *g1;
+            }
+            *g1 += g2.len();
+        });
+        let handle = when!(c1, accounts, limits; g1, accs, lims; {
+            (*g1, accs.into_iter().map(|acc| *acc).collect::<Vec<_>>(), *lims[0])
+        });
+        assert_eq!(handle.wait(), (6, vec![1, 2, 3, 4], 9));
+    }
+
+    #[test]
+    fn when_repeated_read() {
+        let c1 = CownPtr::new(1);
+        let c2 = CownPtr::new(0);
+        let readers = vec![c1.read(), c1.read()];
+        let handle = run_when(tuple_list!(readers, c2.clone()), |tuple_list!(gs, g2)| {
+            *g2 = *gs[0] + *gs[1];
+            *g2
+        });
+        assert_eq!(handle.wait(), 2);
+        assert_eq!(
+            when!(read c1, read c1, c2; g1, g1_, g2; *g1 + *g1_ + *g2).wait(),
+            4
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "a cown is requested more than once")]
+    fn when_repeated_write() {
+        let c1 = CownPtr::new(0);
+        when!(c1, c1; g1, g1_; {
+            *g1 += 1;
+            *g1_ += 1;
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "a cown is requested more than once")]
+    fn when_repeated_read_write() {
+        let c1 = CownPtr::new(0);
+        let cowns = vec![c1.clone(), CownPtr::new(0)];
+        when!(read c1, cowns; g1, gs; gs.into_iter().for_each(|g| *g += *g1));
+    }
+
+    #[test]
+    fn panic_poison() {
+        let c1 = CownPtr::new(0);
+        let c2 = CownPtr::new(0);
+        let panicked = when!(c1, read c2; g1, g2; {
+            *g1 += 1;
+            if *g2 == 0 {
+                panic!("inconsistent");
+            }
+            *g1 += 1;
+        });
+        // The cowns are released to the later behaviors.
+        let handle = when!(c1, c2; g1, g2; {
+            *g2 += 1;
+            *g1
+        });
+        assert_eq!(handle.wait(), 1);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| panicked.wait())).is_err());
+        assert!(c1.is_poisoned());
+        assert!(!c2.is_poisoned());
+
+        c1.clear_poison();
+        assert!(!c1.is_poisoned());
+        when!(c1, read c2; g1, g2; *g1 += *g2).wait();
+        assert!(!c1.is_poisoned());
+        assert_eq!(when!(read c1; g1; *g1).wait(), 2);
+    }
+
+    #[cfg(feature = "boc-trace")]
+    #[test]
+    fn trace() {
+        use cs431_homework::boc::trace::{to_chrome_json, to_dot};
+
+        let scheduler = Arc::new(DeterministicScheduler::new());
+        let runtime = BocRuntime::new(scheduler.clone());
+        let c1 = CownPtr::new(0);
+        let c2 = CownPtr::new(0);
+        runtime.enter(|| {
+            when!(c1; g1; *g1 += 1);
+            when!(read c1, c2; g1, g2; *g2 += *g1);
+            when!(c2; g2; *g2 += 1);
+        });
+        assert_eq!(scheduler.run(), 3);
+
+        let behaviors = runtime.take_trace();
+        assert_eq!(behaviors.len(), 3);
+        let [b0, b1, b2] = [0, 1, 2].map(|i| behaviors[i].id);
+        assert!(behaviors[0].deps.is_empty());
+        assert_eq!(behaviors[1].deps, [b0]);
+        assert_eq!(
+            behaviors[1].cowns.iter().filter(|(_, read)| *read).count(),
+            1
+        );
+        assert_eq!(behaviors[2].deps, [b1]);
+        for behavior in &behaviors {
+            assert!(behavior.enqueued <= behavior.started.unwrap());
+            assert!(behavior.started <= behavior.finished);
+        }
+        assert!(runtime.take_trace().is_empty());
+
+        let dot = to_dot(&behaviors);
+        assert!(dot.contains(&format!("b{b0} -> b{b1};")));
+        assert!(dot.contains(&format!("b{b1} -> b{b2};")));
+        let json = to_chrome_json(&behaviors);
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert_eq!(json.matches("\"ph\":\"X\"").count(), 3);
+    }
+
+    #[cfg(feature = "boc-trace")]
+    #[test]
+    fn trace_capacity() {
+        use cs431_homework::boc::trace::CAPACITY;
+
+        let scheduler = Arc::new(DeterministicScheduler::new());
+        let runtime = BocRuntime::new(scheduler.clone());
+        let c = CownPtr::new(0);
+        runtime.enter(|| {
+            for _ in 0..CAPACITY + 1 {
+                when!(c; g; *g += 1);
+            }
+        });
+        assert_eq!(scheduler.run(), CAPACITY + 1);
+
+        // The oldest record is dropped.
+        let behaviors = runtime.take_trace();
+        assert_eq!(behaviors.len(), CAPACITY);
+        assert_eq!(behaviors[0].deps, [behaviors[0].id - 1]);
+    }
+
+    #[test]
+    fn get_cloned_try_unwrap() {
+        let runtime = BocRuntime::new(RayonScheduler);
+        let c1 = CownPtr::new(vec![0]);
+        let c1_ = c1.clone();
+        runtime.enter(|| {
+            for i in 1..64 {
+                when!(c1; g1; g1.push(i));
+            }
+            assert_eq!(c1.get_cloned(), (0..64).collect::<Vec<_>>());
+        });
+        let c1 = c1.try_unwrap().unwrap_err();
+        drop(c1_);
+        runtime.wait_quiescent();
+        assert_eq!(c1.try_unwrap().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn run_when_batch_order() {
+        const ACCOUNTS: usize = 16;
+        let log = CownPtr::new(Vec::new());
+        let accounts = (0..ACCOUNTS).map(|_| CownPtr::new(0)).collect::<Vec<_>>();
+        let handles = run_when_batch((0..1024).map(|i| {
+            let cowns = tuple_list!(
+                log.clone(),
+                accounts[i % ACCOUNTS].clone(),
+                accounts[(i + 1 + i / ACCOUNTS % (ACCOUNTS - 1)) % ACCOUNTS].read()
+            );
+            // Annotated, so that the closure is generic over the lifetime of the references.
+            let f = move |(log, (src, (dst, ()))): (&mut Vec<_>, (&mut usize, (&usize, ())))| {
+                log.push(i);
+                *src = src.wrapping_add(1 + *dst);
+                i
+            };
+            (cowns, f)
+        }));
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.wait(), i);
+        }
+        assert_eq!(log.get_cloned(), (0..1024).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn run_when_batch_concurrent() {
+        const BATCHES: usize = 256;
+        const BATCH: usize = 4;
+        let log = CownPtr::new(Vec::new());
+        thread::scope(|s| {
+            let log_ = log.clone();
+            let _ = s.spawn(move || {
+                for i in 0..BATCHES * BATCH {
+                    run_when(log_.clone(), move |log: &mut Vec<_>| log.push(None));
+                    if i % BATCH == 0 {
+                        thread::yield_now();
+                    }
+                }
+            });
+            for i in 0..BATCHES {
+                let _ = run_when_batch((0..BATCH).map(|j| {
+                    let f = move |log: &mut Vec<_>| log.push(Some(i * BATCH + j));
+                    (log.clone(), f)
+                }));
+            }
+        });
+        // The behaviors of a batch are not interleaved with the concurrent ones.
+        let log = log.get_cloned();
+        assert_eq!(log.len(), 2 * BATCHES * BATCH);
+        let batched = log.iter().enumerate().filter_map(|(k, i)| Some((k, (*i)?)));
+        for (k, i) in batched.filter(|(_, i)| i % BATCH == 0) {
+            let expected = (i..i + BATCH).map(Some).collect::<Vec<_>>();
+            assert_eq!(log[k..k + BATCH], expected);
+        }
+    }
+
+    #[test]
+    fn deterministic_scheduler() {
+        fn run() -> Vec<usize> {
+            let scheduler = Arc::new(DeterministicScheduler::new());
+            let runtime = BocRuntime::new(scheduler.clone());
+            let log = CownPtr::new(Vec::new());
+            let cowns = (0..4).map(CownPtr::new).collect::<Vec<_>>();
+            runtime.enter(|| {
+                for c in cowns.iter().rev() {
+                    let log = log.clone();
+                    when!(c; g; {
+                        *g += 10;
+                        let g = *g;
+                        when!(log; log; log.push(g));
+                    });
+                }
+                when!(log; log; log.push(0));
+            });
+            assert_eq!(scheduler.run(), 9);
+            let result = runtime.run_when(tuple_list!(log), |tuple_list!(log)| log.clone());
+            assert_eq!(scheduler.run(), 1);
+            result.wait()
+        }
+
+        // The behaviors run in the order they are scheduled.
+        assert_eq!(run(), vec![0, 13, 12, 11, 10]);
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn priority() {
+        let scheduler = Arc::new(DeterministicScheduler::new());
+        let runtime = BocRuntime::new(scheduler.clone());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let c1 = CownPtr::new(());
+        let push = |i| {
+            let log = log.clone();
+            move |_: &mut ()| log.lock().unwrap().push(i)
+        };
+        for (i, priority) in [Priority::Low, Priority::Normal, Priority::High]
+            .into_iter()
+            .enumerate()
+        {
+            runtime.run_when_with_priority(priority, CownPtr::new(()), push(i));
+        }
+        // The behaviors on a cown run in order.
+        runtime.run_when_with_priority(Priority::Low, c1.clone(), push(3));
+        runtime.run_when_with_priority(Priority::High, c1.clone(), push(4));
+        assert_eq!(scheduler.run(), 5);
+        assert_eq!(*log.lock().unwrap(), [2, 1, 0, 3, 4]);
+    }
+
+    #[test]
+    fn rayon_priority() {
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let runtime = BocRuntime::new(RayonScheduler);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let push = |i| {
+            let log = log.clone();
+            move |_: &mut ()| log.lock().unwrap().push(i)
+        };
+        // The jobs are spawned on the only worker, and run after it returns from `install`.
+        pool.install(|| {
+            runtime.run_when_with_priority(Priority::High, CownPtr::new(()), push(0));
+            for i in 1..4 {
+                runtime.run_when_with_priority(Priority::Normal, CownPtr::new(()), push(i));
+            }
+        });
+        runtime.wait_quiescent();
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 4);
+        assert_eq!(log[0], 0);
+    }
+
+    #[test]
+    fn thread_pool_scheduler() {
+        let pool = Arc::new(ThreadPool::new(2));
+        let runtime = BocRuntime::new(pool.clone());
+        let c1 = CownPtr::new(0);
+        let handles = (0..64)
+            .map(|_| runtime.enter(|| when!(c1; g1; { *g1 += 1; *g1 })))
+            .collect::<Vec<_>>();
+        let results = handles.into_iter().map(|h| h.wait()).collect::<Vec<_>>();
+        assert_eq!(results, (1..=64).collect::<Vec<_>>());
+        pool.join();
+    }
+
+    #[test]
+    fn wait_quiescent() {
+        /// Creates `2^depth - 1` behaviors in total, from inside the behaviors.
+        fn spawn_tree(c: CownPtr<usize>, depth: usize) {
+            if depth == 0 {
+                return;
+            }
+            let c_ = c.clone();
+            when!(c_; g; {
+                *g += 1;
+                spawn_tree(c.clone(), depth - 1);
+                spawn_tree(c.clone(), depth - 1);
+            });
+        }
+
+        let runtime = BocRuntime::new(RayonScheduler);
+        let c1 = CownPtr::new(0);
+        runtime.enter(|| spawn_tree(c1.clone(), 8));
+        runtime.wait_quiescent();
+
+        // All behaviors are completed, so this is scheduled after them.
+        assert_eq!(when!(c1; g1; *g1).wait(), 255);
+    }
+
     #[test]
     fn fibonacci() {
         let (send_finish, recv_finish) = bounded(0);
@@ -0,0 +1,182 @@
+#![cfg(not(feature = "check-loom"))]
+
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering::*;
+use std::thread::{scope, sleep};
+use std::time::Duration;
+
+use cs431_homework::hazard_era::{Shield, alloc, collect, dealloc, retire};
+use stack::Stack;
+
+#[test]
+fn counter() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    let count = AtomicPtr::new(alloc(0usize));
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                for _ in 0..ITER {
+                    let shield = Shield::default();
+                    loop {
+                        let cur_ptr = shield.protect(&count);
+                        let value = unsafe { *cur_ptr };
+                        let new_ptr = alloc(value + 1);
+                        if count
+                            .compare_exchange(cur_ptr, new_ptr, AcqRel, Acquire)
+                            .is_ok()
+                        {
+                            unsafe { retire(cur_ptr) };
+                            break;
+                        } else {
+                            let _ = unsafe { dealloc(new_ptr) };
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let cur = count.load(Acquire);
+    // exclusive access
+    assert_eq!(unsafe { *cur }, THREADS * ITER);
+    unsafe { retire(cur) };
+}
+
+// like `counter`, but trigger interesting interleaving using `sleep` and always call `collect`.
+#[test]
+fn counter_sleep() {
+    const THREADS: usize = 4;
+    const ITER: usize = 1024 * 4;
+
+    let count = AtomicPtr::new(alloc(0usize));
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                for _ in 0..ITER {
+                    let shield = Shield::default();
+                    loop {
+                        let cur_ptr = {
+                            let mut cur = count.load(Relaxed);
+                            while let Err(new) = shield.try_protect(cur, &count) {
+                                sleep(Duration::from_micros(1));
+                                cur = new;
+                            }
+                            cur
+                        };
+                        sleep(Duration::from_micros(1));
+                        let value = unsafe { *cur_ptr };
+                        let new_ptr = alloc(value + 1);
+                        if count
+                            .compare_exchange(cur_ptr, new_ptr, AcqRel, Acquire)
+                            .is_ok()
+                        {
+                            unsafe { retire(cur_ptr) };
+                            collect();
+                            break;
+                        } else {
+                            let _ = unsafe { dealloc(new_ptr) };
+                        }
+                    }
+                }
+            });
+        }
+    });
+    let cur = count.load(Acquire);
+    // exclusive access
+    assert_eq!(unsafe { *cur }, THREADS * ITER);
+    unsafe { retire(cur) };
+}
+
+#[test]
+fn stack() {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 16;
+
+    let stack = Stack::default();
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                for i in 0..ITER {
+                    stack.push(i);
+                    assert!(stack.try_pop().is_some());
+                    collect();
+                }
+            });
+        }
+    });
+    assert!(stack.try_pop().is_none());
+}
+
+mod stack {
+    use core::mem::MaybeUninit;
+    use core::ptr;
+    use core::sync::atomic::AtomicPtr;
+    use core::sync::atomic::Ordering::*;
+
+    use cs431_homework::hazard_era::{Shield, alloc, dealloc, retire};
+
+    /// Treiber's lock-free stack.
+    #[derive(Debug, Default)]
+    pub struct Stack<T> {
+        head: AtomicPtr<Node<T>>,
+    }
+
+    #[derive(Debug)]
+    struct Node<T> {
+        data: MaybeUninit<T>,
+        next: *mut Node<T>,
+    }
+
+    unsafe impl<T: Send> Send for Node<T> {}
+    unsafe impl<T: Sync> Sync for Node<T> {}
+
+    impl<T> Stack<T> {
+        pub fn push(&self, t: T) {
+            let new = alloc(Node {
+                data: MaybeUninit::new(t),
+                next: ptr::null_mut(),
+            });
+
+            let mut head = self.head.load(Relaxed);
+
+            loop {
+                unsafe { (*new).next = head };
+
+                match self.head.compare_exchange(head, new, Release, Relaxed) {
+                    Ok(_) => break,
+                    Err(current) => head = current,
+                }
+            }
+        }
+
+        pub fn try_pop(&self) -> Option<T> {
+            let shield = Shield::default();
+            loop {
+                let head_ptr = shield.protect(&self.head);
+                let head_ref = unsafe { head_ptr.as_ref() }?;
+
+                if self
+                    .head
+                    .compare_exchange(head_ptr, head_ref.next, Relaxed, Relaxed)
+                    .is_ok()
+                {
+                    let data = unsafe { head_ref.data.assume_init_read() };
+                    unsafe { retire(head_ptr) };
+                    return Some(data);
+                }
+            }
+        }
+    }
+
+    impl<T> Drop for Stack<T> {
+        fn drop(&mut self) {
+            let mut o_curr = *self.head.get_mut();
+            while !o_curr.is_null() {
+                let curr = unsafe { dealloc(o_curr) };
+                drop(unsafe { curr.data.assume_init() });
+                o_curr = curr.next;
+            }
+        }
+    }
+}
@@ -4,6 +4,7 @@ use std::thread::{scope, sleep};
 use std::time::Duration;
 
 use cs431_homework::hazard_pointer::{Shield, collect, retire};
+use cs431_homework::{HpQueue, HpStack};
 #[cfg(feature = "check-loom")]
 use loom::sync::atomic::{AtomicPtr, Ordering::*};
 use queue::Queue;
@@ -155,6 +156,88 @@ fn stack_queue() {
     assert!(stack.try_pop().is_none());
 }
 
+#[test]
+fn hp_stack() {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 16;
+
+    let stack = HpStack::default();
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                for i in 0..ITER {
+                    stack.push(i);
+                    assert!(stack.try_pop().is_some());
+                    collect();
+                }
+            });
+        }
+    });
+    assert!(stack.is_empty());
+    assert!(stack.try_pop().is_none());
+}
+
+#[test]
+fn hp_queue() {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 32;
+
+    let queue = HpQueue::default();
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                for i in 0..ITER {
+                    queue.push(i);
+                    assert!(queue.try_pop().is_some());
+                    collect();
+                }
+            });
+        }
+    });
+    assert!(queue.is_empty());
+    assert!(queue.try_pop().is_none());
+}
+
+// every pushed value should be popped exactly once, and the values pushed by a thread should be
+// popped from the queue in order.
+#[test]
+fn hp_stack_queue_values() {
+    const THREADS: usize = 8;
+    const ITER: usize = 1024 * 16;
+
+    let stack = HpStack::default();
+    let queue = HpQueue::default();
+    scope(|s| {
+        for t in 0..THREADS {
+            let stack = &stack;
+            let queue = &queue;
+            let _ = s.spawn(move || {
+                for i in 0..ITER {
+                    stack.push((t, i));
+                    queue.push((t, i));
+                }
+            });
+        }
+    });
+
+    let mut popped = vec![Vec::new(); THREADS];
+    while let Some((t, i)) = queue.try_pop() {
+        popped[t].push(i);
+    }
+    for values in &popped {
+        assert_eq!(*values, (0..ITER).collect::<Vec<_>>());
+    }
+
+    let mut popped = vec![Vec::new(); THREADS];
+    while let Some((t, i)) = stack.try_pop() {
+        popped[t].push(i);
+    }
+    for values in &mut popped {
+        values.reverse();
+        assert_eq!(*values, (0..ITER).collect::<Vec<_>>());
+    }
+}
+
 mod sync {
     use core::ptr;
 
@@ -278,7 +361,7 @@ mod stack {
     unsafe impl<T: Send> Send for Node<T> {}
     unsafe impl<T: Sync> Sync for Node<T> {}
 
-    impl<T> Stack<T> {
+    impl<T: Send> Stack<T> {
         pub fn push(&self, t: T) {
             let new = Box::leak(Box::new(Node {
                 data: MaybeUninit::new(t),
@@ -372,7 +455,7 @@ mod queue {
         }
     }
 
-    impl<T> Queue<T> {
+    impl<T: Send> Queue<T> {
         pub fn push(&self, t: T) {
             let new = Box::leak(Box::new(Node {
                 data: MaybeUninit::new(t),
@@ -0,0 +1,37 @@
+use cs431_homework::test::adt::set;
+use cs431_homework::{ConcurrentSet, HpListSet};
+
+#[test]
+fn smoke() {
+    let set = HpListSet::new();
+    assert!(set.insert(1));
+    assert!(set.insert(3));
+    assert!(set.insert(2));
+    assert!(!set.insert(2));
+    assert!(set.remove(&2));
+    assert!(!set.remove(&2));
+    assert!(set.contains(&1));
+    assert!(!set.contains(&2));
+    assert!(set.contains(&3));
+    assert!(set.remove(&3));
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    set::stress_sequential::<_, HpListSet<u8>>(STEPS);
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 16;
+    set::stress_concurrent::<_, HpListSet<u8>>(THREADS, STEPS);
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 16;
+    set::log_concurrent::<_, HpListSet<u8>>(THREADS, STEPS);
+}
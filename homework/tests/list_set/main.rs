@@ -2,4 +2,5 @@
 #![feature(cfg_sanitize)]
 
 mod fine_grained;
+mod hp_list_set;
 mod optimistic_fine_grained;
@@ -58,6 +58,20 @@ fn thread_pool_drop_block() {
     assert_eq!(counter.load(Ordering::Relaxed), NUM_JOBS);
 }
 
+/// A job dropping the pool does not join its own thread.
+#[test]
+fn thread_pool_drop_in_job() {
+    let pool = ThreadPool::new(NUM_THREADS);
+    let (pool_sender, pool_receiver) = bounded(1);
+    let (done_sender, done_receiver) = bounded(1);
+    pool.execute(move || {
+        drop(pool_receiver.recv().unwrap());
+        done_sender.send(()).unwrap();
+    });
+    pool_sender.send(pool).unwrap();
+    done_receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+}
+
 /// This indirectly tests if the worker threads' `JoinHandle`s are joined when the pool is
 /// dropped.
 #[test]